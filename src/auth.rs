@@ -0,0 +1,122 @@
+use crate::loader::{ApiKeyLocation, Auth};
+use anyhow::{bail, Context, Result};
+use md5::Md5;
+use reqwest::{Method, RequestBuilder, Url};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+
+/// Applies schemes that need no round-trip with the server.
+pub fn apply(builder: RequestBuilder, auth: &Auth) -> RequestBuilder {
+    match auth {
+        Auth::Basic { username, password } => builder.basic_auth(username, password.as_ref()),
+        Auth::Bearer { token } => builder.bearer_auth(token),
+        Auth::ApiKey {
+            name,
+            value,
+            location: ApiKeyLocation::Header,
+        } => builder.header(name.as_str(), value.as_str()),
+        Auth::ApiKey {
+            name,
+            value,
+            location: ApiKeyLocation::Query,
+        } => builder.query(&[(name, value)]),
+        Auth::None | Auth::Digest { .. } | Auth::OAuth2(_) | Auth::AwsSigv4(_) => builder,
+    }
+}
+
+/// Builds the `Authorization` header answering a `WWW-Authenticate: Digest …` challenge (RFC 7616).
+pub fn digest_authorization(
+    challenge: &str,
+    method: &Method,
+    url: &Url,
+    username: &str,
+    password: &str,
+) -> Result<String> {
+    let cnonce = format!("{:016x}", fastrand::u64(..));
+    digest_authorization_with(challenge, method, url, username, password, &cnonce)
+}
+
+/// Like `digest_authorization`, with the client nonce fixed to `cnonce`.
+pub fn digest_authorization_with(
+    challenge: &str,
+    method: &Method,
+    url: &Url,
+    username: &str,
+    password: &str,
+    cnonce: &str,
+) -> Result<String> {
+    let params = challenge
+        .trim()
+        .strip_prefix("Digest")
+        .map(parse_challenge)
+        .context("not a Digest challenge")?;
+    let realm = params.get("realm").map(String::as_str).unwrap_or("");
+    let nonce = params
+        .get("nonce")
+        .context("Digest challenge without nonce")?;
+    let algorithm = params.get("algorithm").map(String::as_str).unwrap_or("MD5");
+    let hash: fn(&str) -> String = match algorithm.to_ascii_uppercase().as_str() {
+        "MD5" | "MD5-SESS" => |s| hex::encode(Md5::digest(s.as_bytes())),
+        "SHA-256" | "SHA-256-SESS" => |s| hex::encode(Sha256::digest(s.as_bytes())),
+        other => bail!("unsupported Digest algorithm '{other}'"),
+    };
+    let qop = params
+        .get("qop")
+        .map(|q| q.split(',').any(|v| v.trim() == "auth"));
+
+    let uri = match url.query() {
+        Some(q) => format!("{}?{q}", url.path()),
+        None => url.path().to_string(),
+    };
+    let nc = "00000001";
+
+    let mut ha1 = hash(&format!("{username}:{realm}:{password}"));
+    if algorithm.to_ascii_uppercase().ends_with("-SESS") {
+        ha1 = hash(&format!("{ha1}:{nonce}:{cnonce}"));
+    }
+    let ha2 = hash(&format!("{method}:{uri}"));
+    let response = match qop {
+        Some(true) => hash(&format!("{ha1}:{nonce}:{nc}:{cnonce}:auth:{ha2}")),
+        Some(false) => bail!("Digest challenge only offers unsupported qop values"),
+        None => hash(&format!("{ha1}:{nonce}:{ha2}")),
+    };
+
+    let mut header = format!(
+        r#"Digest username="{username}", realm="{realm}", nonce="{nonce}", uri="{uri}", algorithm={algorithm}, response="{response}""#
+    );
+    if qop.is_some() {
+        header.push_str(&format!(r#", qop=auth, nc={nc}, cnonce="{cnonce}""#));
+    }
+    if let Some(opaque) = params.get("opaque") {
+        header.push_str(&format!(r#", opaque="{opaque}""#));
+    }
+    Ok(header)
+}
+
+/// Splits `realm="x", nonce="y", qop="auth,auth-int"` into its parameters, honoring quotes.
+pub fn parse_challenge(s: &str) -> HashMap<String, String> {
+    let mut out = HashMap::new();
+    let mut rest = s.trim();
+    while let Some(eq) = rest.find('=') {
+        let key = rest[..eq]
+            .trim()
+            .trim_start_matches(',')
+            .trim()
+            .to_ascii_lowercase();
+        rest = rest[eq + 1..].trim_start();
+        let value = if let Some(quoted) = rest.strip_prefix('"') {
+            let end = quoted.find('"').unwrap_or(quoted.len());
+            let v = &quoted[..end];
+            rest = quoted.get(end + 1..).unwrap_or("");
+            v
+        } else {
+            let end = rest.find(',').unwrap_or(rest.len());
+            let v = rest[..end].trim();
+            rest = &rest[end..];
+            v
+        };
+        out.insert(key, value.to_string());
+        rest = rest.trim_start().trim_start_matches(',');
+    }
+    out
+}
@@ -0,0 +1,511 @@
+use crate::{cli::header, loader, runner};
+use anyhow::{bail, Context, Result};
+use console::{style, Emoji};
+use indicatif::{ProgressBar, ProgressStyle};
+use std::{
+    collections::BTreeMap,
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+use tabled::settings::Style as TableStyle;
+use tabled::{Table, Tabled};
+
+/// How hard and how long to hammer a spell: a request count, a duration or staged profile.
+pub struct BenchOptions {
+    /// Workers in closed mode; the cap on requests in flight in rate mode.
+    pub concurrency: usize,
+    pub requests: Option<u64>,
+    pub duration: Option<Duration>,
+    /// Requests per second, started whatever the response times.
+    pub rate: Option<f64>,
+    pub stages: Vec<loader::Stage>,
+    pub thresholds: Vec<loader::Threshold>,
+}
+
+/// One request: how long it took and the status it got, or why it failed.
+struct Sample {
+    latency: Duration,
+    result: Result<u16, &'static str>,
+}
+
+fn error_kind(e: &reqwest::Error) -> &'static str {
+    if e.is_timeout() {
+        "timeout"
+    } else if e.is_connect() {
+        "connect error"
+    } else if e.is_body() || e.is_decode() {
+        "body error"
+    } else {
+        "request error"
+    }
+}
+
+async fn send(prepared: &runner::Prepared) -> Sample {
+    let sent = Instant::now();
+    let result = exchange(prepared).await;
+    Sample {
+        latency: sent.elapsed(),
+        result,
+    }
+}
+
+/// Sends one request, answering a Digest challenge as `qwest run` does, and reads the body.
+async fn exchange(prepared: &runner::Prepared) -> Result<u16, &'static str> {
+    let request = prepared.build(None).map_err(|_| "request error")?;
+    let (method, url) = (request.method().clone(), request.url().clone());
+    let mut resp = prepared
+        .client()
+        .execute(request)
+        .await
+        .map_err(|e| error_kind(&e))?;
+    let answer = prepared
+        .digest_answer(&resp, &method, &url)
+        .map_err(|_| "digest error")?;
+    if let Some(answer) = answer {
+        // Drain the 401 so its connection can be reused.
+        let _ = resp.bytes().await;
+        let request = prepared.build(Some(&answer)).map_err(|_| "request error")?;
+        resp = prepared
+            .client()
+            .execute(request)
+            .await
+            .map_err(|e| error_kind(&e))?;
+    }
+    let status = resp.status().as_u16();
+    resp.bytes()
+        .await
+        .map(|_| status)
+        .map_err(|e| error_kind(&e))
+}
+
+/// The stages' target at `elapsed`, ramping linearly from the previous stage's (0 first);
+/// `None` once every stage is over.
+fn target_at(stages: &[loader::Stage], elapsed: Duration) -> Option<f64> {
+    let (mut from, mut start) = (0.0, Duration::ZERO);
+    for stage in stages {
+        let end = start + stage.duration;
+        if elapsed < end {
+            let done = (elapsed - start).as_secs_f64() / stage.duration.as_secs_f64();
+            return Some(from + (stage.target - from) * done);
+        }
+        (from, start) = (stage.target, end);
+    }
+    None
+}
+
+/// When to stop sending, shared by every worker.
+struct Limits {
+    started: Instant,
+    deadline: Option<Instant>,
+    requests: Option<u64>,
+    issued: AtomicU64,
+}
+
+impl Limits {
+    /// Claims the next request, or says the run is over.
+    fn claim(&self) -> bool {
+        !(self.deadline.is_some_and(|d| Instant::now() >= d)
+            || self
+                .requests
+                .is_some_and(|n| self.issued.fetch_add(1, Ordering::Relaxed) >= n))
+    }
+
+    fn done(&self) -> bool {
+        self.deadline.is_some_and(|d| Instant::now() >= d)
+            || self
+                .requests
+                .is_some_and(|n| self.issued.load(Ordering::Relaxed) >= n)
+    }
+}
+
+/// Fires the spell with a shared client, prints the report and checks the thresholds.
+pub async fn run(
+    cfg: &loader::Config,
+    spell: &str,
+    project: &str,
+    env: &str,
+    opts: &BenchOptions,
+) -> Result<()> {
+    let req = cfg
+        .requests
+        .iter()
+        .find(|r| r.name == spell)
+        .with_context(|| format!("No spell named '{spell}'"))?;
+    let session = runner::Session::new(&cfg.api, project, env)?;
+    let prepared = Arc::new(runner::prepare(&session, &cfg.api, project, env, req).await?);
+    // Surface body errors once rather than from every worker.
+    prepared.build(None)?;
+
+    let stages = Arc::new(opts.stages.clone());
+    let profile_len = stages
+        .iter()
+        .try_fold(Duration::ZERO, |total, s| total.checked_add(s.duration))
+        .context("bench stages last too long")?;
+    let load = match (opts.requests, opts.duration) {
+        (Some(n), _) => format!("{n} requests"),
+        (None, Some(d)) => format!("{} s", d.as_secs_f64()),
+        (None, None) if !stages.is_empty() => format!(
+            "{} stages over {} s",
+            stages.len(),
+            profile_len.as_secs_f64()
+        ),
+        (None, None) => "until interrupted".into(),
+    };
+    let mode = match opts.rate {
+        Some(_) if !stages.is_empty() => "ramping request rate".to_string(),
+        Some(rate) => format!("{rate} req/s"),
+        None if !stages.is_empty() => "ramping workers".to_string(),
+        None => format!("{} workers", opts.concurrency),
+    };
+    header(
+        Emoji("⚡", "[bench]"),
+        format!("Benchmarking '{spell}' – {load}, {mode}"),
+    );
+
+    let pb = match opts.requests {
+        Some(n) => ProgressBar::new(n).with_style(
+            ProgressStyle::with_template("{bar:40.cyan/blue} {pos}/{len} · {per_sec} · {elapsed}")
+                .unwrap(),
+        ),
+        None => ProgressBar::new_spinner().with_style(
+            ProgressStyle::with_template("{spinner:.green} {pos} requests · {per_sec} · {elapsed}")
+                .unwrap(),
+        ),
+    };
+    pb.enable_steady_tick(Duration::from_millis(100));
+
+    let started = Instant::now();
+    let deadline = opts
+        .duration
+        .or((!stages.is_empty()).then_some(profile_len))
+        .map(|d| started.checked_add(d).context("bench duration is too long"))
+        .transpose()?;
+    let limits = Arc::new(Limits {
+        started,
+        deadline,
+        requests: opts.requests,
+        issued: AtomicU64::new(0),
+    });
+    let (samples, dropped) = match opts.rate {
+        Some(rate) => open_model(&prepared, &limits, &stages, rate, opts.concurrency, &pb).await?,
+        None => (
+            closed_model(&prepared, &limits, &stages, opts.concurrency, &pb).await?,
+            0,
+        ),
+    };
+    let elapsed = started.elapsed();
+    pb.finish_and_clear();
+
+    let stats = Stats::new(samples, elapsed);
+    print_report(&stats);
+    if dropped > 0 {
+        println!(
+            "{}",
+            style(format!(
+                "⚠ {dropped} requests not sent: all {} workers were busy, raise -c",
+                opts.concurrency
+            ))
+            .yellow()
+        );
+    }
+    check_thresholds(&stats, &opts.thresholds)
+}
+
+/// Each worker sends its next request as soon as the previous one is answered. With stages,
+/// worker `i` only runs while the target is above `i`.
+async fn closed_model(
+    prepared: &Arc<runner::Prepared>,
+    limits: &Arc<Limits>,
+    stages: &Arc<Vec<loader::Stage>>,
+    concurrency: usize,
+    pb: &ProgressBar,
+) -> Result<Vec<Sample>> {
+    let workers = match stages.iter().map(|s| s.target).reduce(f64::max) {
+        Some(peak) => peak.ceil() as usize,
+        None => concurrency.max(1),
+    };
+    let mut set = tokio::task::JoinSet::new();
+    for i in 0..workers {
+        let (prepared, limits, stages, pb) =
+            (prepared.clone(), limits.clone(), stages.clone(), pb.clone());
+        set.spawn(async move {
+            let mut samples = Vec::new();
+            loop {
+                if !stages.is_empty() {
+                    match target_at(&stages, limits.started.elapsed()) {
+                        None => break,
+                        Some(target) if (i as f64) >= target => {
+                            tokio::time::sleep(Duration::from_millis(20)).await;
+                            continue;
+                        }
+                        Some(_) => {}
+                    }
+                }
+                if !limits.claim() {
+                    break;
+                }
+                samples.push(send(&prepared).await);
+                pb.inc(1);
+            }
+            samples
+        });
+    }
+    let mut samples = Vec::new();
+    while let Some(worker) = set.join_next().await {
+        samples.extend(worker?);
+    }
+    Ok(samples)
+}
+
+/// Starts requests on a fixed schedule (or the stages' ramping rate) with at most
+/// `max_in_flight` outstanding; returns the samples and how many starts were skipped.
+async fn open_model(
+    prepared: &Arc<runner::Prepared>,
+    limits: &Arc<Limits>,
+    stages: &[loader::Stage],
+    rate: f64,
+    max_in_flight: usize,
+    pb: &ProgressBar,
+) -> Result<(Vec<Sample>, u64)> {
+    let in_flight = Arc::new(AtomicUsize::new(0));
+    let mut set = tokio::task::JoinSet::new();
+    let mut dropped = 0;
+    // Requests owed so far; re-reading the rate every tick lets stages ramp smoothly.
+    let mut credit = 0.0;
+    let mut last = Instant::now();
+    loop {
+        let rate = if stages.is_empty() {
+            Some(rate)
+        } else {
+            target_at(stages, limits.started.elapsed())
+        };
+        let Some(rate) = rate else { break };
+        let now = Instant::now();
+        // Don't burst to catch up after a stall.
+        credit = (credit + rate * (now - last).as_secs_f64()).min(rate.max(1.0));
+        last = now;
+        while credit >= 1.0 {
+            credit -= 1.0;
+            if in_flight.fetch_add(1, Ordering::AcqRel) >= max_in_flight.max(1) {
+                in_flight.fetch_sub(1, Ordering::AcqRel);
+                dropped += 1;
+                continue;
+            }
+            // Only starts that are actually sent count towards `-n`.
+            if !limits.claim() {
+                in_flight.fetch_sub(1, Ordering::AcqRel);
+                break;
+            }
+            let (prepared, in_flight, pb) = (prepared.clone(), in_flight.clone(), pb.clone());
+            set.spawn(async move {
+                let sample = send(&prepared).await;
+                in_flight.fetch_sub(1, Ordering::AcqRel);
+                pb.inc(1);
+                sample
+            });
+        }
+        if limits.done() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(1)).await;
+    }
+    let mut samples = Vec::new();
+    while let Some(sample) = set.join_next().await {
+        samples.push(sample?);
+    }
+    Ok((samples, dropped))
+}
+
+/// What a run measured, for the report and the thresholds.
+struct Stats {
+    samples: Vec<Sample>,
+    elapsed: Duration,
+    /// Latencies of the requests that got a response, sorted.
+    latencies: Vec<Duration>,
+    ok: usize,
+}
+
+impl Stats {
+    fn new(samples: Vec<Sample>, elapsed: Duration) -> Stats {
+        let mut latencies: Vec<Duration> = samples
+            .iter()
+            .filter(|s| s.result.is_ok())
+            .map(|s| s.latency)
+            .collect();
+        latencies.sort();
+        let ok = samples
+            .iter()
+            .filter(|s| matches!(s.result, Ok(status) if status < 400))
+            .count();
+        Stats {
+            samples,
+            elapsed,
+            latencies,
+            ok,
+        }
+    }
+
+    fn error_rate(&self) -> f64 {
+        100.0 * (self.samples.len() - self.ok) as f64 / self.samples.len().max(1) as f64
+    }
+
+    fn rps(&self) -> f64 {
+        self.samples.len() as f64 / self.elapsed.as_secs_f64()
+    }
+
+    /// The value a threshold compares: milliseconds, percent or requests per second.
+    fn metric(&self, metric: loader::Metric) -> f64 {
+        let ms = |d: Duration| d.as_secs_f64() * 1000.0;
+        match metric {
+            loader::Metric::Percentile(p) => ms(percentile(&self.latencies, p)),
+            loader::Metric::Min => ms(self.latencies.first().copied().unwrap_or_default()),
+            loader::Metric::Max => ms(self.latencies.last().copied().unwrap_or_default()),
+            loader::Metric::Mean => {
+                ms(self.latencies.iter().sum::<Duration>()) / self.latencies.len().max(1) as f64
+            }
+            loader::Metric::ErrorRate => self.error_rate(),
+            loader::Metric::Rps => self.rps(),
+        }
+    }
+}
+
+fn check_thresholds(stats: &Stats, thresholds: &[loader::Threshold]) -> Result<()> {
+    if thresholds.is_empty() {
+        return Ok(());
+    }
+    header(Emoji("🎯", "[thresholds]"), "Thresholds");
+    let mut failed = 0;
+    for t in thresholds {
+        let actual = stats.metric(t.metric);
+        let unit = match t.metric {
+            loader::Metric::ErrorRate => " %",
+            loader::Metric::Rps => " req/s",
+            _ => " ms",
+        };
+        let line = format!("{} (actual {actual:.1}{unit})", t.source);
+        if t.op.holds(actual, t.value) {
+            println!("{}", style(format!("✓ {line}")).green());
+        } else {
+            failed += 1;
+            println!("{}", style(format!("✗ {line}")).red());
+        }
+    }
+    if failed > 0 {
+        bail!("{failed} of {} bench thresholds failed", thresholds.len());
+    }
+    Ok(())
+}
+
+fn percentile(sorted: &[Duration], p: f64) -> Duration {
+    if sorted.is_empty() {
+        return Duration::ZERO;
+    }
+    let idx = ((p / 100.0) * (sorted.len() - 1) as f64).round() as usize;
+    sorted[idx.min(sorted.len() - 1)]
+}
+
+fn ms(d: Duration) -> String {
+    format!("{:.1} ms", d.as_secs_f64() * 1000.0)
+}
+
+fn print_report(stats: &Stats) {
+    #[derive(Tabled)]
+    struct Summary {
+        requests: usize,
+        duration: String,
+        throughput: String,
+        ok: usize,
+        failed: usize,
+    }
+    #[derive(Tabled)]
+    struct Outcome {
+        outcome: String,
+        count: usize,
+        share: String,
+    }
+    #[derive(Tabled)]
+    struct Latency {
+        min: String,
+        p50: String,
+        p90: String,
+        p95: String,
+        p99: String,
+        max: String,
+    }
+
+    let total = stats.samples.len();
+    let mut outcomes: BTreeMap<String, usize> = BTreeMap::new();
+    for s in &stats.samples {
+        let key = match s.result {
+            Ok(status) => status.to_string(),
+            Err(kind) => kind.to_string(),
+        };
+        *outcomes.entry(key).or_default() += 1;
+    }
+
+    header(Emoji("🧾", "[report]"), "Bench report");
+    let summary = Summary {
+        requests: total,
+        duration: format!("{:.2} s", stats.elapsed.as_secs_f64()),
+        throughput: format!("{:.1} req/s", stats.rps()),
+        ok: stats.ok,
+        failed: total - stats.ok,
+    };
+    println!("{}", Table::new([summary]).with(TableStyle::rounded()));
+
+    let rows: Vec<Outcome> = outcomes
+        .into_iter()
+        .map(|(outcome, count)| Outcome {
+            outcome,
+            count,
+            share: format!("{:.1} %", 100.0 * count as f64 / total.max(1) as f64),
+        })
+        .collect();
+    println!("{}", Table::new(rows).with(TableStyle::rounded()));
+
+    let latencies = &stats.latencies;
+    if latencies.is_empty() {
+        println!("{}", style("No response received").red());
+        return;
+    }
+    let latency = Latency {
+        min: ms(latencies[0]),
+        p50: ms(percentile(latencies, 50.0)),
+        p90: ms(percentile(latencies, 90.0)),
+        p95: ms(percentile(latencies, 95.0)),
+        p99: ms(percentile(latencies, 99.0)),
+        max: ms(latencies[latencies.len() - 1]),
+    };
+    println!("{}", Table::new([latency]).with(TableStyle::rounded()));
+    print_histogram(latencies);
+}
+
+/// Ten equal-width latency buckets between the fastest and slowest response.
+fn print_histogram(sorted: &[Duration]) {
+    const BUCKETS: usize = 10;
+    const WIDTH: usize = 40;
+    let (min, max) = (
+        sorted[0].as_secs_f64(),
+        sorted[sorted.len() - 1].as_secs_f64(),
+    );
+    let step = ((max - min) / BUCKETS as f64).max(f64::EPSILON);
+    let mut counts = [0usize; BUCKETS];
+    for d in sorted {
+        let idx = ((d.as_secs_f64() - min) / step) as usize;
+        counts[idx.min(BUCKETS - 1)] += 1;
+    }
+    let peak = counts.iter().copied().max().unwrap_or(1).max(1);
+    for (i, count) in counts.iter().enumerate() {
+        let upper = Duration::from_secs_f64(min + step * (i + 1) as f64);
+        let bar = "█".repeat(count * WIDTH / peak);
+        println!(
+            "{:>10} │{} {}",
+            ms(upper),
+            style(bar).cyan(),
+            style(count).dim()
+        );
+    }
+}
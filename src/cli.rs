@@ -1,13 +1,16 @@
-use crate::db;
 use crate::env::load_env;
 use crate::share::share;
 use crate::template::TEMPLATE;
+use crate::{bench, cookies, data, db, diff, render};
 use crate::{consts::CONFIG_FILES_LOCATION, loader, runner};
-use anyhow::{bail, Context, Result};
-use clap::{Parser, Subcommand};
+use anyhow::{anyhow, bail, Context, Result};
+use clap::{Args, Parser, Subcommand};
 use console::{style, Emoji};
+use cookie_store::CookieExpiration;
 use indicatif::{ProgressBar, ProgressStyle};
-use std::{fs, fs::File, io::Write, path::PathBuf, process::Command};
+use std::{
+    collections::HashMap, fs, fs::File, io::Write, path::PathBuf, process::Command, time::Duration,
+};
 use tabled::settings::Style as TableStyle;
 use tabled::{Table, Tabled};
 
@@ -42,8 +45,89 @@ enum Cmd {
     Run {
         #[arg(help = "Name of the spell‑book TOML (without .toml)")]
         name: String,
-        #[arg(help = "Name of the spell (request) inside the book")]
+        #[arg(help = "Name of the spell (request) inside the book; omit to cast every spell")]
+        spell_name: Option<String>,
+        #[arg(
+            short,
+            long,
+            requires = "spell_name",
+            help = "Stream the response body to this file"
+        )]
+        output: Option<PathBuf>,
+        #[arg(
+            long,
+            help = "Print total, time-to-first-byte and download times and body sizes"
+        )]
+        timing: bool,
+        #[arg(long, value_enum, default_value_t, requires = "spell_name")]
+        format: runner::OutputFormat,
+        #[arg(
+            long,
+            value_name = "BYTES",
+            help = "Cut printed bodies after this many bytes"
+        )]
+        max_body: Option<usize>,
+        #[arg(long, help = "Never page long bodies through $PAGER")]
+        no_pager: bool,
+        #[arg(
+            long,
+            requires = "spell_name",
+            help = "Exit with 3, 4 or 5 when the response is a 3xx, 4xx or 5xx"
+        )]
+        fail: bool,
+        #[arg(
+            long,
+            value_name = "FILE",
+            requires = "spell_name",
+            conflicts_with_all = ["output", "format", "fail"],
+            help = "Cast the spell once per row of a .csv or .jsonl file, columns as variables"
+        )]
+        data: Option<PathBuf>,
+        #[arg(
+            long,
+            conflicts_with_all = ["output", "data", "fail"],
+            help = "Re-run whenever the book or a file it references is saved"
+        )]
+        watch: bool,
+        #[arg(long, value_name = "N", help = "Stop an event stream after N events")]
+        max_events: Option<u64>,
+        #[arg(
+            long,
+            value_name = "DURATION",
+            value_parser = loader::parse_duration,
+            help = "Stop listening to an event stream after this long, e.g. 30s"
+        )]
+        stream_timeout: Option<Duration>,
+        #[command(flatten)]
+        net: NetworkArgs,
+    },
+    Bench {
+        #[arg(help = "Name of the spell‑book TOML (without .toml)")]
+        name: String,
         spell_name: String,
+        #[arg(
+            short,
+            long,
+            help = "Concurrent workers, or requests in flight with --rate [default: [bench] concurrency or 10]"
+        )]
+        concurrency: Option<usize>,
+        #[arg(
+            short = 'n',
+            long,
+            conflicts_with = "duration",
+            help = "Total requests to send [default: 100]"
+        )]
+        requests: Option<u64>,
+        #[arg(short, long, value_parser = loader::parse_duration, help = "Keep sending for this long, e.g. 30s or 2m")]
+        duration: Option<Duration>,
+        #[arg(
+            long,
+            value_parser = loader::parse_rate,
+            help = "Start this many requests per second, whatever the response times"
+        )]
+        rate: Option<f64>,
+        #[command(flatten)]
+        net: NetworkArgs,
     },
     Create {
         name: String,
@@ -61,6 +145,65 @@ enum Cmd {
         #[command(subcommand)]
         action: VarsCmd,
     },
+    Cookies {
+        #[command(subcommand)]
+        action: CookiesCmd,
+    },
+    Tokens {
+        #[command(subcommand)]
+        action: TokensCmd,
+    },
+    History {
+        #[command(subcommand)]
+        action: HistoryCmd,
+    },
+}
+
+/// Proxy and TLS flags, overriding the book's `[api.proxy]` and `[api.tls]`.
+#[derive(Args)]
+struct NetworkArgs {
+    #[arg(
+        long,
+        help = "Proxy for every request (http://, https:// or socks5://)"
+    )]
+    proxy: Option<String>,
+    #[arg(long, value_delimiter = ',', help = "Hosts that bypass the proxy")]
+    noproxy: Vec<String>,
+    #[arg(long, help = "Extra CA bundle (PEM) to trust")]
+    cacert: Vec<String>,
+    #[arg(long, help = "Client certificate (PEM) for mutual TLS")]
+    cert: Option<String>,
+    #[arg(
+        long,
+        requires = "cert",
+        help = "Private key (PEM) of the client certificate"
+    )]
+    key: Option<String>,
+    #[arg(short = 'k', long, help = "Skip TLS certificate verification")]
+    insecure: bool,
+}
+
+impl NetworkArgs {
+    fn apply(&self, api: &mut loader::Api) {
+        if self.proxy.is_some() || !self.noproxy.is_empty() {
+            let proxy = api.proxy.get_or_insert_with(Default::default);
+            // `--proxy` replaces the book's proxies but keeps the hosts that bypass them.
+            if let Some(url) = &self.proxy {
+                *proxy = loader::Proxy {
+                    url: Some(url.clone()),
+                    no_proxy: std::mem::take(&mut proxy.no_proxy),
+                    ..Default::default()
+                };
+            }
+            proxy.no_proxy.extend(self.noproxy.iter().cloned());
+        }
+        api.tls.ca_files.extend(self.cacert.iter().cloned());
+        if let Some(cert) = &self.cert {
+            api.tls.client_cert = Some(cert.clone());
+            api.tls.client_key = self.key.clone();
+        }
+        api.tls.insecure |= self.insecure;
+    }
 }
 
 #[derive(Subcommand)]
@@ -84,7 +227,71 @@ enum VarsCmd {
     },
 }
 
-fn header<S: AsRef<str>>(emoji: Emoji<'_, '_>, text: S) {
+#[derive(Subcommand)]
+enum CookiesCmd {
+    List {
+        #[arg(long = "project")]
+        project: String,
+    },
+
+    Clear {
+        #[arg(long = "project")]
+        project: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum TokensCmd {
+    List {
+        #[arg(long = "project")]
+        project: String,
+    },
+
+    Clear {
+        #[arg(long = "project")]
+        project: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum HistoryCmd {
+    /// Latest recorded requests; filter by book with the global `--book`.
+    List {
+        #[arg(long)]
+        spell: Option<String>,
+        #[arg(short = 'n', long, default_value_t = 20)]
+        limit: u32,
+    },
+
+    /// Full request and response of one history entry.
+    Show { id: i64 },
+
+    /// Resends a recorded request exactly as it went out.
+    Replay {
+        id: i64,
+        #[arg(long, help = "Compare the new response with the recorded one")]
+        diff: bool,
+    },
+
+    /// Compares the status, headers and JSON bodies of two history entries.
+    Diff {
+        old: i64,
+        new: i64,
+        #[arg(
+            long = "ignore-header",
+            default_value = "date",
+            help = "Header left out of the comparison (repeatable)"
+        )]
+        ignore_headers: Vec<String>,
+    },
+
+    Clear {
+        #[arg(long = "project")]
+        project: String,
+    },
+}
+
+pub(crate) fn header<S: AsRef<str>>(emoji: Emoji<'_, '_>, text: S) {
     println!("{} {}", emoji, style(text.as_ref()).bold().cyan());
 }
 
@@ -162,33 +369,594 @@ fn print_tome(cfg: &loader::Config) -> Result<()> {
     Ok(())
 }
 
-async fn cast_spell(
-    cfg: &loader::Config,
-    spell: &str,
+/// Casts `spell` after re-expanding the book, so placeholders see variables set by earlier spells.
+async fn cast_named(
+    session: &runner::Session,
     project_name: &str,
     env_name: &str,
-) -> Result<()> {
+    vars: &mut HashMap<String, String>,
+    spell: &str,
+    opts: &runner::RunOptions,
+) -> Result<runner::Outcome> {
+    let cfg = load_tome(Some(project_name.to_string()), env_name)?;
     let req = cfg
         .requests
         .iter()
         .find(|r| r.name == spell)
         .with_context(|| format!("No spell named '{spell}'"))?;
+    runner::execute(session, &cfg.api, project_name, env_name, vars, req, opts).await
+}
 
-    let pb = ProgressBar::new_spinner();
+/// Casts `spell` after its dependencies. Failing tests of `spell` itself are left in the
+/// outcome for the caller to report.
+async fn cast_spell(
+    cfg: &loader::Config,
+    spell: &str,
+    project_name: &str,
+    env_name: &str,
+    opts: runner::RunOptions,
+) -> Result<runner::Outcome> {
+    let order = cfg.resolve_order(spell)?;
+    let (deps, target) = order.split_at(order.len() - 1);
+    let deps: Vec<String> = deps.iter().map(|r| r.name.clone()).collect();
+    // A download draws its own progress bar, and machine formats keep stderr for diagnostics.
+    let quiet = opts.output.is_some()
+        || target[0].save_to.is_some()
+        || opts.format != runner::OutputFormat::Transcript;
+
+    let session = runner::Session::new(&cfg.api, project_name, env_name)?;
+    let mut vars = db::load_vars(project_name, env_name).unwrap_or_default();
+
+    let dep_opts = runner::RunOptions {
+        timing: opts.timing,
+        format: match opts.format {
+            runner::OutputFormat::Transcript => runner::OutputFormat::Transcript,
+            _ => runner::OutputFormat::Stderr,
+        },
+        ..Default::default()
+    };
+    for dep in &deps {
+        let outcome = cast_named(&session, project_name, env_name, &mut vars, dep, &dep_opts)
+            .await
+            .with_context(|| format!("casting dependency '{dep}' of '{spell}'"))?;
+        if !outcome.passed() {
+            let err = match outcome.tests {
+                runner::Tests::Failed(e) => e,
+                _ => anyhow!("status {}", outcome.status),
+            };
+            return Err(err.context(format!("dependency '{dep}' of '{spell}' failed")));
+        }
+    }
+
+    let pb = if quiet {
+        ProgressBar::hidden()
+    } else {
+        ProgressBar::new_spinner()
+    };
     pb.set_style(
         ProgressStyle::with_template("{spinner:.green} {msg}")
             .unwrap()
             .tick_chars("🪄✨🔮🧙‍♀️"),
     );
-    pb.set_message(format!("Conjuring '{}'…", req.name));
+    pb.set_message(format!("Conjuring '{spell}'…"));
     pb.enable_steady_tick(std::time::Duration::from_millis(120));
 
-    runner::run_single_request(&cfg.api.base_url, project_name, env_name, req).await?;
-    pb.finish_with_message("Spell resolved ✅");
+    let opts = runner::RunOptions {
+        spinner: Some(pb.clone()),
+        ..opts
+    };
+    let outcome = cast_named(&session, project_name, env_name, &mut vars, spell, &opts).await?;
+    if let runner::Tests::Failed(_) = outcome.tests {
+        pb.abandon_with_message("Spell backfired ❌");
+    } else {
+        pb.finish_with_message("Spell resolved ✅");
+    }
+    Ok(outcome)
+}
+
+/// Casts every spell in file order and prints a report. Returns the outcome of each spell that
+/// got a response, by name in casting order, and a verdict that fails when any spell failed.
+async fn cast_book(
+    project_name: &str,
+    env_name: &str,
+    net: &NetworkArgs,
+    opts: &runner::RunOptions,
+) -> Result<(Vec<(String, runner::Outcome)>, Result<()>)> {
+    let mut cfg = load_tome(Some(project_name.to_string()), env_name)?;
+    net.apply(&mut cfg.api);
+    header(
+        Emoji("📚", "[book]"),
+        format!("Casting every spell in '{}'", cfg.api.name),
+    );
+
+    #[derive(Tabled)]
+    struct ResultRow {
+        #[tabled(rename = "#")]
+        idx: usize,
+        name: String,
+        status: String,
+        duration: String,
+        ttfb: String,
+        size: String,
+        tests: String,
+    }
+
+    let session = runner::Session::new(&cfg.api, project_name, env_name)?;
+    let mut vars = db::load_vars(project_name, env_name).unwrap_or_default();
+    let mut rows: Vec<ResultRow> = Vec::with_capacity(cfg.requests.len());
+    // Spell name → whether it passed; dependencies already cast are not cast again.
+    let mut cast: HashMap<String, bool> = HashMap::new();
+    let mut outcomes = Vec::new();
+
+    for req in &cfg.requests {
+        for step in cfg.resolve_order(&req.name)? {
+            if cast.contains_key(&step.name) {
+                continue;
+            }
+            let idx = rows.len() + 1;
+
+            if let Some(dep) = step
+                .depends_on
+                .iter()
+                .find(|d| cast.get(*d) == Some(&false))
+            {
+                eprintln!(
+                    "{}",
+                    style(format!(
+                        "Skipping '{}': dependency '{dep}' failed",
+                        step.name
+                    ))
+                    .yellow()
+                );
+                cast.insert(step.name.clone(), false);
+                rows.push(ResultRow {
+                    idx,
+                    name: step.name.clone(),
+                    status: "skipped".into(),
+                    duration: "—".into(),
+                    ttfb: "—".into(),
+                    size: "—".into(),
+                    tests: "✗ blocked".into(),
+                });
+                continue;
+            }
+
+            let row = match cast_named(
+                &session,
+                project_name,
+                env_name,
+                &mut vars,
+                &step.name,
+                opts,
+            )
+            .await
+            {
+                Ok(outcome) => {
+                    cast.insert(step.name.clone(), outcome.passed());
+                    let tests = match &outcome.tests {
+                        runner::Tests::Skipped => "—".to_string(),
+                        runner::Tests::Passed => "✓ passed".to_string(),
+                        runner::Tests::Failed(e) => {
+                            eprintln!("{}", style(format!("{e:#}")).yellow());
+                            "✗ failed".to_string()
+                        }
+                    };
+                    let row = ResultRow {
+                        idx,
+                        name: step.name.clone(),
+                        status: outcome.status.as_u16().to_string(),
+                        duration: format!("{} ms", outcome.timing.total.as_millis()),
+                        ttfb: format!("{} ms", outcome.timing.ttfb.as_millis()),
+                        size: format!("{} B", outcome.timing.response_bytes),
+                        tests,
+                    };
+                    outcomes.push((step.name.clone(), outcome));
+                    row
+                }
+                Err(e) => {
+                    cast.insert(step.name.clone(), false);
+                    eprintln!("{}", style(format!("{e:#}")).red());
+                    ResultRow {
+                        idx,
+                        name: step.name.clone(),
+                        status: "error".into(),
+                        duration: "—".into(),
+                        ttfb: "—".into(),
+                        size: "—".into(),
+                        tests: "✗ error".into(),
+                    }
+                }
+            };
+            rows.push(row);
+        }
+    }
+
+    let total = rows.len();
+    let failed = cast.values().filter(|passed| !**passed).count();
+    let table = Table::new(rows).with(TableStyle::rounded()).to_string();
+    header(Emoji("🧾", "[report]"), "Qwest report");
+    println!("{table}");
+
+    if failed > 0 {
+        return Ok((outcomes, Err(anyhow!("{failed} of {total} spells failed"))));
+    }
+    println!(
+        "{}",
+        style(format!("All {total} spells resolved ✅")).green()
+    );
+    Ok((outcomes, Ok(())))
+}
+
+/// Casts `spell` once per data row, each row's columns layered over the book's variables.
+/// Dependencies are cast once, before the first row.
+async fn cast_rows(
+    project_name: &str,
+    spell: &str,
+    env_name: &str,
+    rows: &[data::Row],
+    net: &NetworkArgs,
+    opts: &runner::RunOptions,
+) -> Result<()> {
+    // Placeholders may only make valid TOML once a row fills them in.
+    let mut cfg = load_tome_with(Some(project_name.to_string()), env_name, &rows[0])?;
+    net.apply(&mut cfg.api);
+    let order = cfg.resolve_order(spell)?;
+    header(
+        Emoji("📜", "[data]"),
+        format!("Casting '{spell}' for {} rows", rows.len()),
+    );
+
+    #[derive(Tabled)]
+    struct ResultRow {
+        #[tabled(rename = "#")]
+        idx: usize,
+        row: String,
+        status: String,
+        duration: String,
+        tests: String,
+    }
+
+    let session = runner::Session::new(&cfg.api, project_name, env_name)?;
+    let mut vars = db::load_vars(project_name, env_name).unwrap_or_default();
+    for dep in &order[..order.len() - 1] {
+        let outcome = cast_named(&session, project_name, env_name, &mut vars, &dep.name, opts)
+            .await
+            .with_context(|| format!("casting dependency '{}' of '{spell}'", dep.name))?;
+        if !outcome.passed() {
+            bail!("dependency '{}' of '{spell}' failed", dep.name);
+        }
+    }
+
+    let mut results = Vec::with_capacity(rows.len());
+    let mut failed = 0;
+    for (i, row) in rows.iter().enumerate() {
+        let summary: Vec<String> = row.iter().map(|(k, v)| format!("{k}={v}")).collect();
+        let mut summary = summary.join(" ");
+        if summary.chars().count() > 40 {
+            summary = summary.chars().take(39).collect::<String>() + "…";
+        }
+        eprintln!("{}", style(format!("── row {}: {summary}", i + 1)).dim());
+
+        let outcome = async {
+            let mut cfg = load_tome_with(Some(project_name.to_string()), env_name, row)?;
+            net.apply(&mut cfg.api);
+            let req = cfg
+                .requests
+                .iter()
+                .find(|r| r.name == spell)
+                .with_context(|| format!("No spell named '{spell}'"))?;
+            runner::execute(
+                &session,
+                &cfg.api,
+                project_name,
+                env_name,
+                &mut vars,
+                req,
+                opts,
+            )
+            .await
+        }
+        .await;
+        let (status, duration, tests) = match outcome {
+            Ok(outcome) => {
+                if !outcome.passed() {
+                    failed += 1;
+                }
+                let tests = match &outcome.tests {
+                    runner::Tests::Skipped => "—".to_string(),
+                    runner::Tests::Passed => "✓ passed".to_string(),
+                    runner::Tests::Failed(e) => {
+                        eprintln!("{}", style(format!("{e:#}")).yellow());
+                        "✗ failed".to_string()
+                    }
+                };
+                (
+                    outcome.status.as_u16().to_string(),
+                    format!("{} ms", outcome.timing.total.as_millis()),
+                    tests,
+                )
+            }
+            Err(e) => {
+                failed += 1;
+                eprintln!("{}", style(format!("{e:#}")).red());
+                ("error".into(), "—".into(), "✗ error".into())
+            }
+        };
+        results.push(ResultRow {
+            idx: i + 1,
+            row: summary,
+            status,
+            duration,
+            tests,
+        });
+    }
+
+    header(Emoji("🧾", "[report]"), "Data report");
+    println!("{}", Table::new(results).with(TableStyle::rounded()));
+    if failed > 0 {
+        bail!("{failed} of {} rows failed", rows.len());
+    }
+    println!(
+        "{}",
+        style(format!("All {} rows resolved ✅", rows.len())).green()
+    );
+    Ok(())
+}
+
+/// Files whose change triggers a re-run: the book plus the body and part files it references.
+fn watched_files(project_name: &str, env_name: &str) -> Vec<PathBuf> {
+    let mut files = vec![PathBuf::from(format!(
+        "{CONFIG_FILES_LOCATION}/{project_name}.toml"
+    ))];
+    if let Ok(cfg) = load_tome(Some(project_name.to_string()), env_name) {
+        for req in &cfg.requests {
+            files.extend(req.body_file.iter().map(PathBuf::from));
+            files.extend(
+                req.parts
+                    .iter()
+                    .filter_map(|p| p.file.as_ref())
+                    .map(PathBuf::from),
+            );
+        }
+    }
+    files
+}
+
+fn modified(files: &[PathBuf]) -> Vec<Option<std::time::SystemTime>> {
+    files
+        .iter()
+        .map(|f| fs::metadata(f).and_then(|m| m.modified()).ok())
+        .collect()
+}
+
+/// Re-casts the spell (or the whole book) every time a watched file is saved, showing what
+/// changed in each spell's response since the previous run.
+async fn watch(
+    project_name: &str,
+    spell: Option<&str>,
+    env_name: &str,
+    net: &NetworkArgs,
+    opts: &runner::RunOptions,
+) -> Result<()> {
+    let mut previous: Option<runner::Outcome> = None;
+    let mut previous_book: HashMap<String, runner::Outcome> = HashMap::new();
+    loop {
+        let files = watched_files(project_name, env_name);
+        let stamps = modified(&files);
+        let _ = console::Term::stdout().clear_screen();
+        header(
+            Emoji("👀", "[watch]"),
+            format!("Watching '{project_name}' – save to re-run, Ctrl-C to stop"),
+        );
+
+        let result = match spell {
+            Some(spell) => match load_tome(Some(project_name.to_string()), env_name) {
+                Ok(mut cfg) => {
+                    net.apply(&mut cfg.api);
+                    cast_spell(&cfg, spell, project_name, env_name, opts.clone())
+                        .await
+                        .and_then(|mut outcome| {
+                            if let Some(previous) = &previous {
+                                header(Emoji("🔍", "[diff]"), "Since the previous run");
+                                print_diff(
+                                    previous.into(),
+                                    (&outcome).into(),
+                                    &["date".to_string()],
+                                );
+                            }
+                            // Diff against this run next time, even when its tests failed.
+                            let tests =
+                                std::mem::replace(&mut outcome.tests, runner::Tests::Skipped);
+                            previous = Some(outcome);
+                            match tests {
+                                runner::Tests::Failed(e) => Err(e),
+                                _ => Ok(()),
+                            }
+                        })
+                }
+                Err(e) => Err(e),
+            },
+            None => cast_book(project_name, env_name, net, opts).await.and_then(
+                |(outcomes, verdict)| {
+                    for (name, outcome) in &outcomes {
+                        if let Some(previous) = previous_book.get(name) {
+                            header(
+                                Emoji("🔍", "[diff]"),
+                                format!("'{name}' since the previous run"),
+                            );
+                            print_diff(previous.into(), outcome.into(), &["date".to_string()]);
+                        }
+                    }
+                    previous_book = outcomes.into_iter().collect();
+                    verdict
+                },
+            ),
+        };
+        if let Err(e) = result {
+            eprintln!("{}", style(format!("{e:#}")).red());
+        }
+
+        while modified(&files) == stamps {
+            tokio::time::sleep(Duration::from_millis(300)).await;
+        }
+        // Editors often write a file in several steps.
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+}
+
+fn format_timestamp(at: i64) -> String {
+    chrono::DateTime::from_timestamp(at, 0)
+        .map(|d| {
+            d.with_timezone(&chrono::Local)
+                .format("%Y-%m-%d %H:%M:%S")
+                .to_string()
+        })
+        .unwrap_or_default()
+}
+
+fn print_history_body(body: Option<&[u8]>, content_type: Option<&str>, missing: &str) {
+    match body {
+        Some([]) => {}
+        Some(bytes) => println!(
+            "{}",
+            render::body(bytes, content_type, None, render::stdout_is_tty())
+        ),
+        None => println!("{}", style(missing).dim()),
+    }
+}
+
+fn print_changes(changes: &[diff::Change]) {
+    for change in changes {
+        match change {
+            diff::Change::Added(path, v) => println!("{}", style(format!("+ {path}: {v}")).green()),
+            diff::Change::Removed(path, v) => println!("{}", style(format!("- {path}: {v}")).red()),
+            diff::Change::Changed(path, a, b) => {
+                println!("{}", style(format!("~ {path}: {a} → {b}")).yellow())
+            }
+        }
+    }
+}
+
+/// A response as far as diffing is concerned.
+struct Snapshot<'a> {
+    status: u16,
+    headers: &'a [(String, String)],
+    body: Option<&'a [u8]>,
+}
+
+impl<'a> From<&'a db::HistoryEntry> for Snapshot<'a> {
+    fn from(entry: &'a db::HistoryEntry) -> Self {
+        Snapshot {
+            status: entry.status,
+            headers: &entry.response_headers,
+            body: entry.response_body.as_deref(),
+        }
+    }
+}
+
+impl<'a> From<&'a runner::Outcome> for Snapshot<'a> {
+    fn from(outcome: &'a runner::Outcome) -> Self {
+        Snapshot {
+            status: outcome.status.as_u16(),
+            headers: &outcome.headers,
+            body: outcome.body.as_deref(),
+        }
+    }
+}
+
+/// Prints what changed from history entry `old` to `new`.
+fn diff_history(old: &db::HistoryEntry, new: &db::HistoryEntry, ignore_headers: &[String]) {
+    header(Emoji("🔍", "[diff]"), format!("#{} → #{}", old.id, new.id));
+    print_diff(old.into(), new.into(), ignore_headers);
+}
+
+/// Prints what changed in status, headers and body from `old` to `new`.
+fn print_diff(old: Snapshot, new: Snapshot, ignore_headers: &[String]) {
+    let mut changed = false;
+
+    if old.status != new.status {
+        changed = true;
+        println!(
+            "{}",
+            style(format!("~ status: {} → {}", old.status, new.status)).yellow()
+        );
+    }
+
+    let headers = diff::headers(old.headers, new.headers, ignore_headers);
+    changed |= !headers.is_empty();
+    print_changes(&headers);
+
+    match (old.body, new.body) {
+        (Some(a), Some(b)) if a == b => {}
+        (Some(a), Some(b)) => {
+            changed = true;
+            let parse = |bytes: &[u8], snapshot: &Snapshot| {
+                let text = render::decode(bytes, render::content_type(snapshot.headers));
+                serde_json::from_str::<serde_json::Value>(&text).ok()
+            };
+            match (parse(a, &old), parse(b, &new)) {
+                (Some(a), Some(b)) => print_changes(&diff::json(&a, &b)),
+                _ => println!(
+                    "{}",
+                    style(format!("~ body: {} B → {} B", a.len(), b.len())).yellow()
+                ),
+            }
+        }
+        _ => println!("{}", style("body not recorded, skipped").dim()),
+    }
+
+    if !changed {
+        println!("{}", style("No differences ✅").green());
+    }
+}
+
+fn show_history(entry: &db::HistoryEntry) -> Result<()> {
+    header(
+        Emoji("🗂", "[history]"),
+        format!(
+            "#{} '{}' from {} @ {} – {}",
+            entry.id,
+            entry.spell,
+            entry.project,
+            entry.env,
+            format_timestamp(entry.created_at)
+        ),
+    );
+    println!("{} {} {}", style("→").bold(), entry.method, entry.url);
+    for (k, v) in &entry.request_headers {
+        println!("{}: {}", style(k).dim(), v);
+    }
+    println!();
+    print_history_body(
+        entry.request_body.as_deref(),
+        render::content_type(&entry.request_headers),
+        "<streamed body not recorded>",
+    );
+    println!();
+
+    runner::print_head(
+        reqwest::StatusCode::from_u16(entry.status)?,
+        &entry.response_headers,
+    );
+    print_history_body(
+        entry.response_body.as_deref(),
+        render::content_type(&entry.response_headers),
+        "<body was saved to a file>",
+    );
+    println!("\n{}", style(format!("⏱ {} ms", entry.duration_ms)).dim());
     Ok(())
 }
 
 fn load_tome(project_name: Option<String>, env_name: &str) -> Result<loader::Config> {
+    load_tome_with(project_name, env_name, &[])
+}
+
+/// Loads the book with `overrides` taking precedence over the environment and stored variables.
+fn load_tome_with(
+    project_name: Option<String>,
+    env_name: &str,
+    overrides: &[(String, String)],
+) -> Result<loader::Config> {
     let fname = project_name.clone().unwrap_or_else(|| "default".into());
     let path = format!("{}/{}.toml", CONFIG_FILES_LOCATION, fname);
     let mut vars = load_env()?;
@@ -196,6 +964,7 @@ fn load_tome(project_name: Option<String>, env_name: &str) -> Result<loader::Con
     for (k, v) in db_vars {
         vars.entry(k).or_insert(v);
     }
+    vars.extend(overrides.iter().cloned());
 
     loader::load_config(&path, vars).with_context(|| format!("loading spell-book '{}.toml'", fname))
 }
@@ -222,9 +991,88 @@ pub async fn handle() -> Result<()> {
             }
             print_tome(&cfg)?;
         }
-        Cmd::Run { name, spell_name } => {
-            let cfg = load_tome(Some(name.clone()), &env_name)?;
-            cast_spell(&cfg, &spell_name, &name, &env_name).await?;
+        Cmd::Run {
+            name,
+            spell_name,
+            output,
+            timing,
+            format,
+            fail,
+            max_body,
+            no_pager,
+            data,
+            watch,
+            max_events,
+            stream_timeout,
+            net,
+        } => {
+            let opts = runner::RunOptions {
+                output,
+                timing,
+                format,
+                max_body,
+                // Paging each spell of a book or row would stop the run at every long body.
+                pager: !no_pager && spell_name.is_some() && data.is_none() && !watch,
+                max_events,
+                stream_timeout,
+                spinner: None,
+            };
+            if watch {
+                return self::watch(&name, spell_name.as_deref(), &env_name, &net, &opts).await;
+            }
+            match (spell_name, data) {
+                (Some(spell_name), Some(data)) => {
+                    let rows = data::load_rows(&data)?;
+                    cast_rows(&name, &spell_name, &env_name, &rows, &net, &opts).await?;
+                }
+                (Some(spell_name), None) => {
+                    let mut cfg = load_tome(Some(name.clone()), &env_name)?;
+                    net.apply(&mut cfg.api);
+                    let outcome = cast_spell(&cfg, &spell_name, &name, &env_name, opts).await?;
+                    if let runner::Tests::Failed(e) = outcome.tests {
+                        return Err(e);
+                    }
+                    if fail {
+                        if let code @ 3..=5 = outcome.status.as_u16() / 100 {
+                            std::process::exit(code.into());
+                        }
+                    }
+                }
+                (None, _) => cast_book(&name, &env_name, &net, &opts).await?.1?,
+            }
+        }
+        Cmd::Bench {
+            name,
+            spell_name,
+            concurrency,
+            requests,
+            duration,
+            rate,
+            net,
+        } => {
+            let mut cfg = load_tome(Some(name.clone()), &env_name)?;
+            net.apply(&mut cfg.api);
+            let book = cfg.bench.clone();
+            // A request count, duration or rate on the command line replaces the book's stages,
+            // and so does a worker count when the stage targets are workers.
+            let replaces_stages = requests.is_some()
+                || duration.is_some()
+                || rate.is_some()
+                || (concurrency.is_some() && book.rate.is_none());
+            let stages = if replaces_stages {
+                Vec::new()
+            } else {
+                book.stages
+            };
+            let opts = bench::BenchOptions {
+                concurrency: concurrency.or(book.concurrency).unwrap_or(10),
+                requests: requests.or((duration.is_none() && stages.is_empty()).then_some(100)),
+                duration,
+                rate: rate.or(book.rate),
+                stages,
+                thresholds: book.thresholds,
+            };
+            bench::run(&cfg, &spell_name, &name, &env_name, &opts).await?;
         }
         Cmd::Create { name } => {
             let mut path = PathBuf::from(CONFIG_FILES_LOCATION);
@@ -287,7 +1135,7 @@ path   = "/docs"
         Cmd::Share { name } => {
             println!(
                 "http://localhost:8080/config/{}",
-                share("http://localhost:8080/config", &name, &TEMPLATE)
+                share("http://localhost:8080/config", &name, TEMPLATE)
                     .await
                     .with_context(|| format!("sharing spell‑book '{}'", name))?
             );
@@ -340,6 +1188,188 @@ path   = "/docs"
                 );
             }
         },
+        Cmd::Cookies { action } => match action {
+            CookiesCmd::List { project } => {
+                let store = cookies::load_store(&project, &env_name)?;
+                #[derive(Tabled)]
+                struct Row {
+                    domain: String,
+                    path: String,
+                    name: String,
+                    value: String,
+                    expires: String,
+                }
+                let rows: Vec<Row> = store
+                    .iter_unexpired()
+                    .map(|c| Row {
+                        domain: c.domain.as_cow().unwrap_or_default().into_owned(),
+                        path: String::from(&c.path),
+                        name: c.name().to_string(),
+                        value: c.value().to_string(),
+                        expires: match &c.expires {
+                            CookieExpiration::AtUtc(at) => {
+                                chrono::DateTime::from_timestamp(at.unix_timestamp(), 0)
+                                    .map(|d| d.to_rfc3339())
+                                    .unwrap_or_default()
+                            }
+                            CookieExpiration::SessionEnd => "session".into(),
+                        },
+                    })
+                    .collect();
+                if rows.is_empty() {
+                    println!(
+                        "{}",
+                        style(format!(
+                            "No cookies for project='{project}', env='{env_name}'"
+                        ))
+                        .yellow()
+                    );
+                } else {
+                    let table = Table::new(rows).with(TableStyle::rounded()).to_string();
+                    header(
+                        Emoji("🍪", "[cookies]"),
+                        format!("Cookies for {project} @ {env_name}"),
+                    );
+                    println!("{table}");
+                }
+            }
+            CookiesCmd::Clear { project } => {
+                db::delete_cookie_jar(&project, &env_name)?;
+                println!(
+                    "{}",
+                    style(format!("Cleared cookies for {project} @ {env_name}")).yellow()
+                );
+            }
+        },
+        Cmd::History { action } => match action {
+            HistoryCmd::List { spell, limit } => {
+                let entries =
+                    db::list_history(cli.book.as_deref(), &env_name, spell.as_deref(), limit)?;
+                if entries.is_empty() {
+                    println!(
+                        "{}",
+                        style(format!("No history for env='{env_name}'")).yellow()
+                    );
+                } else {
+                    #[derive(Tabled)]
+                    struct Row {
+                        id: i64,
+                        when: String,
+                        book: String,
+                        spell: String,
+                        request: String,
+                        status: u16,
+                        duration: String,
+                    }
+                    let rows: Vec<Row> = entries
+                        .into_iter()
+                        .map(|e| Row {
+                            id: e.id,
+                            when: format_timestamp(e.created_at),
+                            book: e.project,
+                            spell: e.spell,
+                            request: format!("{} {}", e.method, e.url),
+                            status: e.status,
+                            duration: format!("{} ms", e.duration_ms),
+                        })
+                        .collect();
+                    let table = Table::new(rows).with(TableStyle::rounded()).to_string();
+                    header(Emoji("🗂", "[history]"), format!("History @ {env_name}"));
+                    println!("{table}");
+                }
+            }
+            HistoryCmd::Show { id } => {
+                let entry =
+                    db::load_history(id)?.with_context(|| format!("No history entry #{id}"))?;
+                show_history(&entry)?;
+            }
+            HistoryCmd::Replay { id, diff } => {
+                let entry =
+                    db::load_history(id)?.with_context(|| format!("No history entry #{id}"))?;
+                let cfg = load_tome(Some(entry.project.clone()), &entry.env)?;
+                header(
+                    Emoji("↻", "[replay]"),
+                    format!("Replaying #{id} '{}' from {}", entry.spell, entry.project),
+                );
+                let session = runner::Session::new(&cfg.api, &entry.project, &entry.env)?;
+                let replayed = runner::replay(&session, &cfg.api, &entry).await?;
+                if replayed.id != 0 {
+                    println!("{}", style(format!("Recorded as #{}", replayed.id)).dim());
+                }
+                if diff {
+                    diff_history(&entry, &replayed, &["date".to_string()]);
+                }
+            }
+            HistoryCmd::Diff {
+                old,
+                new,
+                ignore_headers,
+            } => {
+                let load = |id: i64| {
+                    db::load_history(id)?.with_context(|| format!("No history entry #{id}"))
+                };
+                diff_history(&load(old)?, &load(new)?, &ignore_headers);
+            }
+            HistoryCmd::Clear { project } => {
+                db::delete_history(&project, &env_name)?;
+                println!(
+                    "{}",
+                    style(format!("Cleared history for {project} @ {env_name}")).yellow()
+                );
+            }
+        },
+        Cmd::Tokens { action } => match action {
+            TokensCmd::List { project } => {
+                let tokens = db::list_tokens(&project, &env_name)?;
+                if tokens.is_empty() {
+                    println!(
+                        "{}",
+                        style(format!(
+                            "No OAuth2 tokens for project='{project}', env='{env_name}'"
+                        ))
+                        .yellow()
+                    );
+                } else {
+                    #[derive(Tabled)]
+                    struct Row {
+                        client: String,
+                        refreshable: String,
+                        expires: String,
+                    }
+                    let now = chrono::Utc::now().timestamp();
+                    let rows: Vec<Row> = tokens
+                        .into_iter()
+                        .map(|t| Row {
+                            client: t.cache_key,
+                            refreshable: if t.refresh_token.is_some() {
+                                "yes"
+                            } else {
+                                "no"
+                            }
+                            .into(),
+                            expires: match t.expires_at {
+                                Some(at) if at <= now => "expired".into(),
+                                Some(at) => format!("in {} s", at - now),
+                                None => "never".into(),
+                            },
+                        })
+                        .collect();
+                    let table = Table::new(rows).with(TableStyle::rounded()).to_string();
+                    header(
+                        Emoji("🔑", "[tokens]"),
+                        format!("OAuth2 tokens for {project} @ {env_name}"),
+                    );
+                    println!("{table}");
+                }
+            }
+            TokensCmd::Clear { project } => {
+                db::delete_tokens(&project, &env_name)?;
+                println!(
+                    "{}",
+                    style(format!("Cleared OAuth2 tokens for {project} @ {env_name}")).yellow()
+                );
+            }
+        },
     }
     Ok(())
 }
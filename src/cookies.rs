@@ -0,0 +1,22 @@
+use anyhow::{Context, Result};
+use reqwest_cookie_store::CookieStore;
+use std::io::BufReader;
+
+/// Loads the cookie jar persisted for `project` @ `env`, or an empty one.
+pub fn load_store(project: &str, env: &str) -> Result<CookieStore> {
+    match crate::db::load_cookie_jar(project, env)? {
+        Some(json) => cookie_store::serde::json::load(BufReader::new(json.as_bytes()))
+            .map_err(|e| anyhow::anyhow!("{e}"))
+            .with_context(|| format!("parsing cookie jar of {project} @ {env}")),
+        None => Ok(CookieStore::default()),
+    }
+}
+
+/// Persists every cookie of `store`, session cookies included, so logins survive between runs.
+pub fn save_store(project: &str, env: &str, store: &CookieStore) -> Result<()> {
+    let mut json = Vec::new();
+    cookie_store::serde::json::save_incl_expired_and_nonpersistent(store, &mut json)
+        .map_err(|e| anyhow::anyhow!("{e}"))
+        .context("serializing cookie jar")?;
+    crate::db::save_cookie_jar(project, env, &String::from_utf8(json)?)
+}
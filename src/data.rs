@@ -0,0 +1,73 @@
+use anyhow::{bail, Context, Result};
+use serde_json::Value;
+use std::{fs, path::Path};
+
+/// One input row: column name → value.
+pub type Row = Vec<(String, String)>;
+
+/// Reads the rows of a `.csv` (header line first) or `.jsonl` (one object per line) file.
+pub fn load_rows(path: &Path) -> Result<Vec<Row>> {
+    let ext = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or("")
+        .to_ascii_lowercase();
+    let rows = match ext.as_str() {
+        "csv" => load_csv(path),
+        "jsonl" | "ndjson" => load_jsonl(path),
+        _ => bail!("data file should end in .csv or .jsonl"),
+    }
+    .with_context(|| format!("reading data file {}", path.display()))?;
+    if rows.is_empty() {
+        bail!("data file {} has no rows", path.display());
+    }
+    Ok(rows)
+}
+
+fn load_csv(path: &Path) -> Result<Vec<Row>> {
+    let mut reader = csv::Reader::from_path(path)?;
+    let columns: Vec<String> = reader
+        .headers()?
+        .iter()
+        .map(|c| c.trim().to_string())
+        .collect();
+    let mut rows = Vec::new();
+    for record in reader.records() {
+        let record = record?;
+        rows.push(
+            columns
+                .iter()
+                .cloned()
+                .zip(record.iter().map(str::to_string))
+                .collect(),
+        );
+    }
+    Ok(rows)
+}
+
+fn load_jsonl(path: &Path) -> Result<Vec<Row>> {
+    let mut rows = Vec::new();
+    for (i, line) in fs::read_to_string(path)?.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let value: Value = serde_json::from_str(line).with_context(|| format!("line {}", i + 1))?;
+        let Value::Object(map) = value else {
+            bail!("line {} should be a JSON object", i + 1);
+        };
+        rows.push(
+            map.into_iter()
+                .map(|(k, v)| {
+                    // Nested values go in as JSON, ready for a JSON body.
+                    let v = match v {
+                        Value::String(s) => s,
+                        Value::Null => String::new(),
+                        other => other.to_string(),
+                    };
+                    (k, v)
+                })
+                .collect(),
+        );
+    }
+    Ok(rows)
+}
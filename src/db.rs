@@ -23,6 +23,37 @@ fn open_db() -> Result<Connection> {
             PRIMARY KEY (project_name, env, name)
         );
         CREATE INDEX IF NOT EXISTS ix_vars_proj_env ON variables(project_name, env);
+        CREATE TABLE IF NOT EXISTS cookies (
+            project_name TEXT NOT NULL,
+            env TEXT NOT NULL,
+            jar TEXT NOT NULL,
+            PRIMARY KEY (project_name, env)
+        );
+        CREATE TABLE IF NOT EXISTS oauth_tokens (
+            project_name TEXT NOT NULL,
+            env TEXT NOT NULL,
+            cache_key TEXT NOT NULL,
+            access_token TEXT NOT NULL,
+            refresh_token TEXT,
+            expires_at INTEGER,
+            PRIMARY KEY (project_name, env, cache_key)
+        );
+        CREATE TABLE IF NOT EXISTS history (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            project_name TEXT NOT NULL,
+            env TEXT NOT NULL,
+            spell TEXT NOT NULL,
+            method TEXT NOT NULL,
+            url TEXT NOT NULL,
+            request_headers TEXT NOT NULL,
+            request_body BLOB,
+            status INTEGER NOT NULL,
+            response_headers TEXT NOT NULL,
+            response_body BLOB,
+            duration_ms INTEGER NOT NULL,
+            created_at INTEGER NOT NULL
+        );
+        CREATE INDEX IF NOT EXISTS ix_history_proj_env ON history(project_name, env, id);
         "#,
     )?;
     Ok(conn)
@@ -85,3 +116,258 @@ pub fn delete_var(project: &str, env: &str, name: &str) -> Result<()> {
     )?;
     Ok(())
 }
+
+pub fn load_cookie_jar(project: &str, env: &str) -> Result<Option<String>> {
+    let conn = open_db()?;
+    let jar = conn
+        .query_row(
+            "SELECT jar FROM cookies WHERE project_name=?1 AND env=?2",
+            params![project, env],
+            |row| row.get::<_, String>(0),
+        )
+        .optional()?;
+    Ok(jar)
+}
+
+pub fn save_cookie_jar(project: &str, env: &str, jar: &str) -> Result<()> {
+    let conn = open_db()?;
+    conn.execute(
+        r#"
+        INSERT INTO cookies (project_name, env, jar)
+        VALUES (?1, ?2, ?3)
+        ON CONFLICT(project_name, env)
+        DO UPDATE SET jar=excluded.jar
+        "#,
+        params![project, env, jar],
+    )?;
+    Ok(())
+}
+
+pub fn delete_cookie_jar(project: &str, env: &str) -> Result<()> {
+    let conn = open_db()?;
+    conn.execute(
+        "DELETE FROM cookies WHERE project_name=?1 AND env=?2",
+        params![project, env],
+    )?;
+    Ok(())
+}
+
+pub struct CachedToken {
+    pub cache_key: String,
+    pub access_token: String,
+    pub refresh_token: Option<String>,
+    pub expires_at: Option<i64>,
+}
+
+fn token_from_row(row: &rusqlite::Row) -> rusqlite::Result<CachedToken> {
+    Ok(CachedToken {
+        cache_key: row.get(0)?,
+        access_token: row.get(1)?,
+        refresh_token: row.get(2)?,
+        expires_at: row.get(3)?,
+    })
+}
+
+pub fn load_token(project: &str, env: &str, cache_key: &str) -> Result<Option<CachedToken>> {
+    let conn = open_db()?;
+    let token = conn
+        .query_row(
+            r#"
+            SELECT cache_key, access_token, refresh_token, expires_at FROM oauth_tokens
+            WHERE project_name=?1 AND env=?2 AND cache_key=?3
+            "#,
+            params![project, env, cache_key],
+            token_from_row,
+        )
+        .optional()?;
+    Ok(token)
+}
+
+pub fn list_tokens(project: &str, env: &str) -> Result<Vec<CachedToken>> {
+    let conn = open_db()?;
+    let mut stmt = conn.prepare(
+        r#"
+        SELECT cache_key, access_token, refresh_token, expires_at FROM oauth_tokens
+        WHERE project_name=?1 AND env=?2
+        "#,
+    )?;
+    let rows = stmt.query_map(params![project, env], token_from_row)?;
+    Ok(rows.collect::<rusqlite::Result<_>>()?)
+}
+
+pub fn save_token(project: &str, env: &str, token: &CachedToken) -> Result<()> {
+    let conn = open_db()?;
+    conn.execute(
+        r#"
+        INSERT INTO oauth_tokens (project_name, env, cache_key, access_token, refresh_token, expires_at)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+        ON CONFLICT(project_name, env, cache_key)
+        DO UPDATE SET access_token=excluded.access_token,
+                      refresh_token=excluded.refresh_token,
+                      expires_at=excluded.expires_at
+        "#,
+        params![
+            project,
+            env,
+            token.cache_key,
+            token.access_token,
+            token.refresh_token,
+            token.expires_at
+        ],
+    )?;
+    Ok(())
+}
+
+pub fn delete_token(project: &str, env: &str, cache_key: &str) -> Result<()> {
+    let conn = open_db()?;
+    conn.execute(
+        "DELETE FROM oauth_tokens WHERE project_name=?1 AND env=?2 AND cache_key=?3",
+        params![project, env, cache_key],
+    )?;
+    Ok(())
+}
+
+pub fn delete_tokens(project: &str, env: &str) -> Result<()> {
+    let conn = open_db()?;
+    conn.execute(
+        "DELETE FROM oauth_tokens WHERE project_name=?1 AND env=?2",
+        params![project, env],
+    )?;
+    Ok(())
+}
+
+/// One executed request and the response it got. Headers keep their order and duplicates.
+pub struct HistoryEntry {
+    pub id: i64,
+    pub project: String,
+    pub env: String,
+    pub spell: String,
+    pub method: String,
+    pub url: String,
+    pub request_headers: Vec<(String, String)>,
+    /// Empty without a body; `None` when it was streamed (multipart).
+    pub request_body: Option<Vec<u8>>,
+    pub status: u16,
+    pub response_headers: Vec<(String, String)>,
+    /// `None` when the body was saved to a file instead.
+    pub response_body: Option<Vec<u8>>,
+    pub duration_ms: i64,
+    pub created_at: i64,
+}
+
+const HISTORY_COLUMNS: &str = "id, project_name, env, spell, method, url, request_headers, \
+    request_body, status, response_headers, response_body, duration_ms, created_at";
+
+fn history_from_row(row: &rusqlite::Row) -> rusqlite::Result<HistoryEntry> {
+    let headers = |idx: usize| -> rusqlite::Result<Vec<(String, String)>> {
+        let json: String = row.get(idx)?;
+        serde_json::from_str(&json).map_err(|e| {
+            rusqlite::Error::FromSqlConversionFailure(idx, rusqlite::types::Type::Text, e.into())
+        })
+    };
+    Ok(HistoryEntry {
+        id: row.get(0)?,
+        project: row.get(1)?,
+        env: row.get(2)?,
+        spell: row.get(3)?,
+        method: row.get(4)?,
+        url: row.get(5)?,
+        request_headers: headers(6)?,
+        request_body: row.get(7)?,
+        status: row.get(8)?,
+        response_headers: headers(9)?,
+        response_body: row.get(10)?,
+        duration_ms: row.get(11)?,
+        created_at: row.get(12)?,
+    })
+}
+
+/// Records `entry` (its `id` is ignored) and returns the id it was given.
+pub fn save_history(entry: &HistoryEntry) -> Result<i64> {
+    let conn = open_db()?;
+    conn.execute(
+        r#"
+        INSERT INTO history (project_name, env, spell, method, url, request_headers, request_body,
+                             status, response_headers, response_body, duration_ms, created_at)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)
+        "#,
+        params![
+            entry.project,
+            entry.env,
+            entry.spell,
+            entry.method,
+            entry.url,
+            serde_json::to_string(&entry.request_headers)?,
+            entry.request_body,
+            entry.status,
+            serde_json::to_string(&entry.response_headers)?,
+            entry.response_body,
+            entry.duration_ms,
+            entry.created_at
+        ],
+    )?;
+    Ok(conn.last_insert_rowid())
+}
+
+/// Keeps the newest `keep` entries of a project/env, dropping any older than `max_age_days`.
+pub fn prune_history(project: &str, env: &str, keep: u32, max_age_days: Option<u32>) -> Result<()> {
+    let conn = open_db()?;
+    conn.execute(
+        r#"
+        DELETE FROM history WHERE project_name=?1 AND env=?2 AND id NOT IN (
+            SELECT id FROM history WHERE project_name=?1 AND env=?2 ORDER BY id DESC LIMIT ?3
+        )
+        "#,
+        params![project, env, keep],
+    )?;
+    if let Some(days) = max_age_days {
+        let cutoff = chrono::Utc::now().timestamp() - i64::from(days) * 86_400;
+        conn.execute(
+            "DELETE FROM history WHERE project_name=?1 AND env=?2 AND created_at < ?3",
+            params![project, env, cutoff],
+        )?;
+    }
+    Ok(())
+}
+
+/// Newest first; bodies are not loaded.
+pub fn list_history(
+    project: Option<&str>,
+    env: &str,
+    spell: Option<&str>,
+    limit: u32,
+) -> Result<Vec<HistoryEntry>> {
+    let conn = open_db()?;
+    let mut stmt = conn.prepare(
+        r#"
+        SELECT id, project_name, env, spell, method, url, request_headers, NULL,
+               status, response_headers, NULL, duration_ms, created_at
+        FROM history
+        WHERE env=?1 AND (?2 IS NULL OR project_name=?2) AND (?3 IS NULL OR spell=?3)
+        ORDER BY id DESC LIMIT ?4
+        "#,
+    )?;
+    let rows = stmt.query_map(params![env, project, spell, limit], history_from_row)?;
+    Ok(rows.collect::<rusqlite::Result<_>>()?)
+}
+
+pub fn load_history(id: i64) -> Result<Option<HistoryEntry>> {
+    let conn = open_db()?;
+    let entry = conn
+        .query_row(
+            &format!("SELECT {HISTORY_COLUMNS} FROM history WHERE id=?1"),
+            params![id],
+            history_from_row,
+        )
+        .optional()?;
+    Ok(entry)
+}
+
+pub fn delete_history(project: &str, env: &str) -> Result<()> {
+    let conn = open_db()?;
+    conn.execute(
+        "DELETE FROM history WHERE project_name=?1 AND env=?2",
+        params![project, env],
+    )?;
+    Ok(())
+}
@@ -0,0 +1,84 @@
+use serde_json::Value;
+
+/// One difference between two recorded responses, keyed by a `$.a.b[0]` path or a header name.
+#[derive(Debug, PartialEq)]
+pub enum Change {
+    Added(String, Value),
+    Removed(String, Value),
+    Changed(String, Value, Value),
+}
+
+/// Structural diff of two JSON documents; object key order is ignored.
+pub fn json(old: &Value, new: &Value) -> Vec<Change> {
+    let mut changes = Vec::new();
+    walk("$".to_string(), old, new, &mut changes);
+    changes
+}
+
+fn walk(path: String, old: &Value, new: &Value, changes: &mut Vec<Change>) {
+    match (old, new) {
+        (Value::Object(a), Value::Object(b)) => {
+            for (k, va) in a {
+                let p = format!("{path}.{k}");
+                match b.get(k) {
+                    Some(vb) => walk(p, va, vb, changes),
+                    None => changes.push(Change::Removed(p, va.clone())),
+                }
+            }
+            for (k, vb) in b.iter().filter(|(k, _)| !a.contains_key(*k)) {
+                changes.push(Change::Added(format!("{path}.{k}"), vb.clone()));
+            }
+        }
+        (Value::Array(a), Value::Array(b)) => {
+            for i in 0..a.len().max(b.len()) {
+                let p = format!("{path}[{i}]");
+                match (a.get(i), b.get(i)) {
+                    (Some(va), Some(vb)) => walk(p, va, vb, changes),
+                    (Some(va), None) => changes.push(Change::Removed(p, va.clone())),
+                    (None, Some(vb)) => changes.push(Change::Added(p, vb.clone())),
+                    (None, None) => {}
+                }
+            }
+        }
+        _ if old != new => changes.push(Change::Changed(path, old.clone(), new.clone())),
+        _ => {}
+    }
+}
+
+/// Compares headers by lowercased name, joining repeated ones; `ignore` names are skipped.
+pub fn headers(
+    old: &[(String, String)],
+    new: &[(String, String)],
+    ignore: &[String],
+) -> Vec<Change> {
+    let collect = |pairs: &[(String, String)]| {
+        let mut map = serde_json::Map::new();
+        for (k, v) in pairs {
+            let k = k.to_ascii_lowercase();
+            if ignore.iter().any(|i| i.eq_ignore_ascii_case(&k)) {
+                continue;
+            }
+            let joined = match map.remove(&k) {
+                Some(Value::String(prev)) => format!("{prev}, {v}"),
+                _ => v.clone(),
+            };
+            map.insert(k, Value::String(joined));
+        }
+        map
+    };
+    let (a, b) = (collect(old), collect(new));
+    let mut changes = Vec::new();
+    for (k, va) in &a {
+        match b.get(k) {
+            Some(vb) if vb != va => {
+                changes.push(Change::Changed(k.clone(), va.clone(), vb.clone()))
+            }
+            Some(_) => {}
+            None => changes.push(Change::Removed(k.clone(), va.clone())),
+        }
+    }
+    for (k, vb) in b.iter().filter(|(k, _)| !a.contains_key(*k)) {
+        changes.push(Change::Added(k.clone(), vb.clone()));
+    }
+    changes
+}
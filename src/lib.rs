@@ -1,12 +1,23 @@
+pub mod auth;
+pub mod bench;
 pub mod cli;
 pub mod consts;
+pub mod cookies;
+pub mod data;
 pub mod db;
+pub mod diff;
 pub mod dragon;
 pub mod env;
 pub mod loader;
+pub mod oauth;
+pub mod paginate;
+pub mod render;
+pub mod retry;
 pub mod runner;
 pub mod script;
 pub mod share;
+pub mod sigv4;
+pub mod sse;
 pub mod template;
 
 pub use loader::load_config;
@@ -1,13 +1,198 @@
+use anyhow::{anyhow, bail};
 use regex::Regex;
 use serde::Deserialize;
 use serde_json::Value;
-use std::{collections::HashMap, fs, path::PathBuf};
+use std::{collections::HashMap, fs, path::Path, time::Duration};
 
 #[derive(Debug, Deserialize)]
 pub struct Config {
     pub api: Api,
     #[serde(rename = "request")]
     pub requests: Vec<Request>,
+    #[serde(default)]
+    pub bench: Bench,
+}
+
+/// Load profile and pass/fail criteria for `qwest bench`.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct Bench {
+    pub concurrency: Option<usize>,
+    /// Requests per second to start, whatever the response times; workers then only cap
+    /// the requests in flight.
+    #[serde(default, deserialize_with = "rate_num")]
+    pub rate: Option<f64>,
+    /// Ramp from one target to the next; the target is workers, or requests per second
+    /// with `rate`.
+    #[serde(default, rename = "stage")]
+    pub stages: Vec<Stage>,
+    #[serde(default)]
+    pub thresholds: Vec<Threshold>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Stage {
+    #[serde(deserialize_with = "duration_str")]
+    pub duration: Duration,
+    #[serde(deserialize_with = "target_num")]
+    pub target: f64,
+}
+
+/// A criterion such as `p95 < 300ms` or `error_rate < 1%`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(try_from = "String")]
+pub struct Threshold {
+    pub metric: Metric,
+    pub op: Comparison,
+    /// Milliseconds for latencies, percent for `error_rate`, requests per second for `rps`.
+    pub value: f64,
+    pub source: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Metric {
+    /// `p50`, `p95`, `p99.9`… latency percentile.
+    Percentile(f64),
+    Min,
+    Max,
+    Mean,
+    ErrorRate,
+    Rps,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Comparison {
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl Comparison {
+    pub fn holds(self, actual: f64, expected: f64) -> bool {
+        match self {
+            Comparison::Lt => actual < expected,
+            Comparison::Le => actual <= expected,
+            Comparison::Gt => actual > expected,
+            Comparison::Ge => actual >= expected,
+        }
+    }
+}
+
+impl TryFrom<String> for Threshold {
+    type Error = String;
+
+    fn try_from(source: String) -> Result<Self, Self::Error> {
+        let re = Regex::new(r"^\s*([a-z_]+|p[0-9.]+)\s*(<=|>=|<|>)\s*([0-9.]+)\s*(ms|s|%)?\s*$")
+            .expect("valid threshold regex");
+        let caps = re
+            .captures(&source)
+            .ok_or_else(|| format!("invalid threshold '{source}', expected e.g. 'p95 < 300ms'"))?;
+        let metric = match &caps[1] {
+            "min" => Metric::Min,
+            "max" => Metric::Max,
+            "mean" | "avg" => Metric::Mean,
+            "error_rate" => Metric::ErrorRate,
+            "rps" => Metric::Rps,
+            p if p.starts_with('p') => match p[1..].parse::<f64>() {
+                Ok(pct) if (0.0..=100.0).contains(&pct) => Metric::Percentile(pct),
+                _ => return Err(format!("invalid percentile '{p}' in threshold '{source}'")),
+            },
+            other => return Err(format!("unknown metric '{other}' in threshold '{source}'")),
+        };
+        let op = match &caps[2] {
+            "<" => Comparison::Lt,
+            "<=" => Comparison::Le,
+            ">" => Comparison::Gt,
+            _ => Comparison::Ge,
+        };
+        let number: f64 = caps[3]
+            .parse()
+            .map_err(|_| format!("invalid number in threshold '{source}'"))?;
+        let unit = caps.get(4).map(|m| m.as_str());
+        let value = match (metric, unit) {
+            (Metric::ErrorRate, None | Some("%")) | (Metric::Rps, None) => number,
+            (Metric::ErrorRate | Metric::Rps, _) => {
+                return Err(format!("unexpected unit in threshold '{source}'"))
+            }
+            (_, None | Some("ms")) => number,
+            (_, Some("s")) => number * 1000.0,
+            (_, Some(_)) => return Err(format!("unexpected unit in threshold '{source}'")),
+        };
+        Ok(Threshold {
+            metric,
+            op,
+            value,
+            source: source.trim().to_string(),
+        })
+    }
+}
+
+/// Parses `500ms`, `30s`, `2m` or `1h`; a bare number is seconds.
+pub fn parse_duration(s: &str) -> anyhow::Result<Duration> {
+    let s = s.trim();
+    let split = s
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(s.len());
+    let (value, unit) = s.split_at(split);
+    let value: f64 = value
+        .parse()
+        .map_err(|_| anyhow!("invalid duration '{s}'"))?;
+    let secs = match unit {
+        "ms" => value / 1000.0,
+        "" | "s" => value,
+        "m" => value * 60.0,
+        "h" => value * 3600.0,
+        _ => bail!("unknown unit '{unit}' in '{s}' (use ms, s, m or h)"),
+    };
+    Duration::try_from_secs_f64(secs).map_err(|_| anyhow!("duration '{s}' is out of range"))
+}
+
+/// Parses a request rate, which must be above zero.
+pub fn parse_rate(s: &str) -> anyhow::Result<f64> {
+    let rate: f64 = s
+        .trim()
+        .parse()
+        .map_err(|_| anyhow!("invalid rate '{s}'"))?;
+    check_rate(rate)
+}
+
+fn check_rate(rate: f64) -> anyhow::Result<f64> {
+    // A rate of zero would never start a request, and the run would never end.
+    if !(rate > 0.0 && rate.is_finite()) {
+        bail!("rate should be above 0, got {rate}");
+    }
+    Ok(rate)
+}
+
+fn rate_num<'de, D>(de: D) -> Result<Option<f64>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    Option::<f64>::deserialize(de)?
+        .map(check_rate)
+        .transpose()
+        .map_err(serde::de::Error::custom)
+}
+
+fn target_num<'de, D>(de: D) -> Result<f64, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let target = f64::deserialize(de)?;
+    if !(target >= 0.0 && target.is_finite()) {
+        return Err(serde::de::Error::custom(format!(
+            "stage target should be 0 or more, got {target}"
+        )));
+    }
+    Ok(target)
+}
+
+fn duration_str<'de, D>(de: D) -> Result<Duration, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let raw = String::deserialize(de)?;
+    parse_duration(&raw).map_err(serde::de::Error::custom)
 }
 
 #[derive(Debug, Deserialize)]
@@ -19,15 +204,237 @@ pub struct Api {
     pub timeout_ms: Option<u64>,
     #[serde(default)]
     pub follow_redirects: Option<bool>,
+    #[serde(default)]
+    pub max_redirects: Option<usize>,
+    #[serde(default)]
+    pub connect_timeout_ms: Option<u64>,
+    #[serde(default)]
+    pub retry: Option<Retry>,
+    #[serde(default)]
+    pub auth: Option<Auth>,
+    #[serde(default)]
+    pub tls: Tls,
+    #[serde(default)]
+    pub proxy: Option<Proxy>,
+    #[serde(default)]
+    pub history: History,
 }
 
-#[derive(Debug, Deserialize)]
+/// How much of the request/response history is kept in `qwest.sqlite`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct History {
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// Entries kept per project and env; older ones are pruned.
+    #[serde(default = "default_history_keep")]
+    pub keep: u32,
+    pub max_age_days: Option<u32>,
+}
+
+impl Default for History {
+    fn default() -> Self {
+        History {
+            enabled: true,
+            keep: default_history_keep(),
+            max_age_days: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct Tls {
+    /// Extra PEM bundles trusted on top of the built-in roots.
+    #[serde(default)]
+    pub ca_files: Vec<String>,
+    /// PEM client certificate; may also contain the private key.
+    pub client_cert: Option<String>,
+    pub client_key: Option<String>,
+    #[serde(default)]
+    pub insecure: bool,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct Proxy {
+    /// Used for every scheme unless `http` or `https` is set; may be `socks5://…`.
+    pub url: Option<String>,
+    pub http: Option<String>,
+    pub https: Option<String>,
+    #[serde(default)]
+    pub no_proxy: Vec<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Auth {
+    None,
+    Basic {
+        username: String,
+        password: Option<String>,
+    },
+    Bearer {
+        token: String,
+    },
+    ApiKey {
+        name: String,
+        value: String,
+        #[serde(default, rename = "in")]
+        location: ApiKeyLocation,
+    },
+    Digest {
+        username: String,
+        password: String,
+    },
+    #[serde(rename = "oauth2")]
+    OAuth2(OAuth2),
+    #[serde(rename = "aws_sigv4")]
+    AwsSigv4(AwsSigv4),
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct AwsSigv4 {
+    pub region: String,
+    pub service: String,
+    pub access_key: String,
+    pub secret_key: String,
+    pub session_token: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct OAuth2 {
+    pub token_url: String,
+    pub client_id: String,
+    pub client_secret: Option<String>,
+    #[serde(default)]
+    pub scopes: Vec<String>,
+    #[serde(default)]
+    pub grant_type: OAuth2Grant,
+    pub refresh_token: Option<String>,
+    pub audience: Option<String>,
+    #[serde(default)]
+    pub client_auth: ClientAuth,
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OAuth2Grant {
+    #[default]
+    ClientCredentials,
+    RefreshToken,
+}
+
+/// How the client id and secret are sent to the token endpoint.
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ClientAuth {
+    #[default]
+    Basic,
+    Body,
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ApiKeyLocation {
+    #[default]
+    Header,
+    Query,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Retry {
+    #[serde(default = "default_max_attempts")]
+    pub max_attempts: u32,
+    #[serde(default = "default_backoff_ms")]
+    pub backoff_ms: u64,
+    #[serde(default)]
+    pub jitter_ms: u64,
+    #[serde(default = "default_retry_status")]
+    pub on_status: Vec<u16>,
+    #[serde(default = "default_retry_errors")]
+    pub on_errors: Vec<RetryError>,
+    #[serde(default = "default_true")]
+    pub idempotent_only: bool,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RetryError {
+    Timeout,
+    Connect,
+}
+
+fn default_max_attempts() -> u32 {
+    3
+}
+
+fn default_backoff_ms() -> u64 {
+    200
+}
+
+fn default_poll_interval_ms() -> u64 {
+    1000
+}
+
+fn default_poll_attempts() -> u32 {
+    30
+}
+
+fn default_max_pages() -> u32 {
+    10
+}
+
+fn default_page_param() -> String {
+    "page".into()
+}
+
+fn default_first_page() -> u64 {
+    1
+}
+
+fn default_offset_param() -> String {
+    "offset".into()
+}
+
+fn default_retry_status() -> Vec<u16> {
+    vec![429, 502, 503, 504]
+}
+
+fn default_retry_errors() -> Vec<RetryError> {
+    vec![RetryError::Timeout, RetryError::Connect]
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_history_keep() -> u32 {
+    200
+}
+
+#[derive(Debug, Clone, Deserialize)]
 pub struct Header {
     pub key: String,
     pub value: String,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
+pub struct Part {
+    pub name: String,
+    pub value: Option<String>,
+    pub file: Option<String>,
+    pub filename: Option<String>,
+    pub content_type: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BodyType {
+    Json,
+    Text,
+    Xml,
+    Binary,
+}
+
+#[derive(Debug, Clone, Deserialize)]
 pub struct Request {
     pub name: String,
     pub method: String,
@@ -36,8 +443,15 @@ pub struct Request {
     #[serde(default, rename = "header")]
     pub headers: Vec<Header>,
 
-    #[serde(default, deserialize_with = "json_string_opt")]
-    pub body: Option<Value>,
+    #[serde(default)]
+    pub body: Option<String>,
+    #[serde(default)]
+    pub body_type: Option<BodyType>,
+    #[serde(default)]
+    pub body_file: Option<String>,
+
+    #[serde(default, rename = "part")]
+    pub parts: Vec<Part>,
 
     #[serde(default, deserialize_with = "json_string_opt")]
     pub params: Option<Value>,
@@ -47,6 +461,164 @@ pub struct Request {
     pub test_script: Option<String>,
     #[serde(rename = "spell")]
     pub spell: Option<String>,
+    #[serde(default)]
+    pub depends_on: Vec<String>,
+    #[serde(default)]
+    pub save_to: Option<String>,
+
+    #[serde(default)]
+    pub timeout_ms: Option<u64>,
+    #[serde(default)]
+    pub follow_redirects: Option<bool>,
+    #[serde(default)]
+    pub max_redirects: Option<usize>,
+    #[serde(default)]
+    pub connect_timeout_ms: Option<u64>,
+    #[serde(default)]
+    pub retry: Option<Retry>,
+    #[serde(default)]
+    pub auth: Option<Auth>,
+
+    /// Rhai condition on `status`, `headers` and `data`; the spell is re-sent until it holds.
+    #[serde(default)]
+    pub until: Option<String>,
+    #[serde(default = "default_poll_interval_ms")]
+    pub interval_ms: u64,
+    /// Sends before giving up on `until`.
+    #[serde(default = "default_poll_attempts")]
+    pub max_attempts: u32,
+    #[serde(default)]
+    pub paginate: Option<Paginate>,
+    #[serde(default)]
+    pub sse: Option<Sse>,
+}
+
+/// Prints a `text/event-stream` response event by event instead of waiting for its end.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct Sse {
+    /// Stops after this many events.
+    pub max_events: Option<u64>,
+    /// Stops listening after this long; the spell's own `timeout_ms` does not apply.
+    pub timeout_ms: Option<u64>,
+    /// Rhai script run for each event, with `event` and its parsed `data`.
+    pub on_event: Option<String>,
+}
+
+/// Follows a listing's pages and merges their items into the test script's `data`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Paginate {
+    #[serde(flatten)]
+    pub style: PageStyle,
+    /// JSONPath to the items of a page, e.g. `$.data`.
+    pub items: String,
+    #[serde(default = "default_max_pages")]
+    pub max_pages: u32,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "style", rename_all = "snake_case")]
+pub enum PageStyle {
+    /// The `Link: <…>; rel="next"` response header.
+    Link,
+    /// A cursor read from the body (JSONPath) and sent back in a query parameter.
+    Cursor { cursor: String, param: String },
+    Page {
+        #[serde(default = "default_page_param")]
+        param: String,
+        #[serde(default = "default_first_page")]
+        start: u64,
+    },
+    /// The number of items already received, added to `start`.
+    Offset {
+        #[serde(default = "default_offset_param")]
+        param: String,
+        #[serde(default)]
+        start: u64,
+    },
+}
+
+impl Request {
+    /// The inline body parsed as JSON, unless the spell declares another `body_type`.
+    pub fn json_body(&self) -> anyhow::Result<Option<Value>> {
+        match (&self.body, self.body_type) {
+            (Some(raw), None | Some(BodyType::Json)) => Ok(Some(serde_json::from_str(raw)?)),
+            _ => Ok(None),
+        }
+    }
+
+    fn validate(&self) -> anyhow::Result<()> {
+        let sources = [
+            self.body.is_some(),
+            self.body_file.is_some(),
+            !self.parts.is_empty(),
+        ];
+        if sources.iter().filter(|s| **s).count() > 1 {
+            bail!(
+                "spell '{}' can only have one of `body`, `body_file` or `part`",
+                self.name
+            );
+        }
+        self.json_body()
+            .map_err(|e| anyhow!("spell '{}' has an invalid JSON body: {e}", self.name))?;
+        Ok(())
+    }
+
+    /// Makes relative `body_file` and part `file` paths relative to `dir`.
+    fn resolve_files(&mut self, dir: &Path) {
+        let files = self
+            .body_file
+            .iter_mut()
+            .chain(self.parts.iter_mut().filter_map(|p| p.file.as_mut()));
+        for file in files {
+            if Path::new(file.as_str()).is_relative() {
+                *file = dir.join(&*file).to_string_lossy().into_owned();
+            }
+        }
+    }
+}
+
+impl Config {
+    /// Returns `spell` preceded by every spell it (transitively) depends on, in casting order.
+    pub fn resolve_order(&self, spell: &str) -> anyhow::Result<Vec<&Request>> {
+        let mut order = Vec::new();
+        let mut stack = Vec::new();
+        let req = self
+            .requests
+            .iter()
+            .find(|r| r.name == spell)
+            .ok_or_else(|| anyhow!("No spell named '{spell}'"))?;
+        self.visit(req, &mut stack, &mut order)?;
+        Ok(order)
+    }
+
+    fn visit<'a>(
+        &'a self,
+        req: &'a Request,
+        stack: &mut Vec<&'a str>,
+        order: &mut Vec<&'a Request>,
+    ) -> anyhow::Result<()> {
+        if order.iter().any(|r| r.name == req.name) {
+            return Ok(());
+        }
+        if let Some(pos) = stack.iter().position(|n| *n == req.name) {
+            let mut cycle = stack[pos..].to_vec();
+            cycle.push(&req.name);
+            bail!("dependency cycle: {}", cycle.join(" → "));
+        }
+
+        stack.push(&req.name);
+        for dep in &req.depends_on {
+            let dep_req = self
+                .requests
+                .iter()
+                .find(|r| &r.name == dep)
+                .ok_or_else(|| anyhow!("spell '{}' depends on unknown spell '{dep}'", req.name))?;
+            self.visit(dep_req, stack, order)?;
+        }
+        stack.pop();
+        order.push(req);
+        Ok(())
+    }
 }
 
 fn json_string_opt<'de, D>(de: D) -> Result<Option<Value>, D::Error>
@@ -65,7 +637,14 @@ where
 pub fn load_config(path: &str, vars: HashMap<String, String>) -> anyhow::Result<Config> {
     let raw = fs::read_to_string(path)?;
     let expanded = expand_placeholders(&raw, vars)?;
-    Ok(toml::from_str(&expanded)?)
+    let mut config: Config = toml::from_str(&expanded)?;
+    // Files referenced by a spell sit next to its book, whatever the current directory.
+    let dir = Path::new(path).parent().unwrap_or(Path::new("."));
+    for req in &mut config.requests {
+        req.validate()?;
+        req.resolve_files(dir);
+    }
+    Ok(config)
 }
 
 fn expand_placeholders(raw: &str, vars: HashMap<String, String>) -> anyhow::Result<String> {
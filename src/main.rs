@@ -1,12 +1,23 @@
+mod auth;
+mod bench;
 mod cli;
 mod consts;
+mod cookies;
+mod data;
 mod db;
+mod diff;
 mod dragon;
 mod env;
 mod loader;
+mod oauth;
+mod paginate;
+mod render;
+mod retry;
 mod runner;
 mod script;
 mod share;
+mod sigv4;
+mod sse;
 mod template;
 
 #[tokio::main]
@@ -0,0 +1,158 @@
+use crate::db::{self, CachedToken};
+use crate::loader::{ClientAuth, OAuth2, OAuth2Grant};
+use anyhow::{bail, Context, Result};
+use colored::Colorize;
+use reqwest::Client;
+use serde::Deserialize;
+
+/// Tokens expiring within this many seconds are treated as already expired.
+const EXPIRY_SKEW_SECS: i64 = 30;
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    expires_in: Option<i64>,
+    refresh_token: Option<String>,
+}
+
+/// Identifies the cached token of one client and scope set within a project and env.
+fn cache_key(cfg: &OAuth2) -> String {
+    format!(
+        "{} {} {}",
+        cfg.token_url,
+        cfg.client_id,
+        cfg.scopes.join(" ")
+    )
+}
+
+/// Returns a valid access token, from the cache when possible, otherwise by refreshing the
+/// cached token or running the configured grant.
+pub async fn access_token(
+    client: &Client,
+    project: &str,
+    env: &str,
+    cfg: &OAuth2,
+) -> Result<String> {
+    let key = cache_key(cfg);
+    let now = chrono::Utc::now().timestamp();
+    let cached = db::load_token(project, env, &key)?;
+
+    if let Some(token) = &cached {
+        match token.expires_at {
+            Some(at) if at - EXPIRY_SKEW_SECS <= now => {
+                eprintln!("{}", "🔑 OAuth2 token expired – renewing".yellow());
+            }
+            _ => return Ok(token.access_token.clone()),
+        }
+    }
+
+    let cached_refresh = cached.and_then(|t| t.refresh_token);
+    let (token, form) = match cached_refresh {
+        Some(refresh) => {
+            let form = vec![
+                ("grant_type", "refresh_token".into()),
+                ("refresh_token", refresh),
+            ];
+            match request_token(client, cfg, form.clone()).await {
+                Ok(token) => (token, form),
+                // An expired or revoked refresh token is useless: forget it and start over.
+                Err(e) => {
+                    eprintln!(
+                        "{}",
+                        format!("🔑 OAuth2 refresh failed ({e}) – requesting a new token").yellow()
+                    );
+                    db::delete_token(project, env, &key)?;
+                    let form = grant_form(cfg)?;
+                    (request_token(client, cfg, form.clone()).await?, form)
+                }
+            }
+        }
+        None => {
+            let form = grant_form(cfg)?;
+            (request_token(client, cfg, form.clone()).await?, form)
+        }
+    };
+
+    let expires_at = token.expires_in.map(|secs| now + secs);
+    match token.expires_in {
+        Some(secs) => eprintln!(
+            "{}",
+            format!("🔑 OAuth2 token acquired, expires in {secs} s").green()
+        ),
+        None => eprintln!("{}", "🔑 OAuth2 token acquired".green()),
+    }
+
+    db::save_token(
+        project,
+        env,
+        &CachedToken {
+            cache_key: key,
+            access_token: token.access_token.clone(),
+            // Servers may omit the refresh token on refresh; keep using the previous one.
+            refresh_token: token.refresh_token.or_else(|| {
+                form.iter()
+                    .find(|(k, _)| *k == "refresh_token")
+                    .map(|(_, v)| v.clone())
+            }),
+            expires_at,
+        },
+    )?;
+    Ok(token.access_token)
+}
+
+/// The form of the configured grant.
+fn grant_form(cfg: &OAuth2) -> Result<Vec<(&'static str, String)>> {
+    Ok(match cfg.grant_type {
+        OAuth2Grant::RefreshToken => {
+            let refresh = cfg
+                .refresh_token
+                .clone()
+                .context("oauth2 refresh_token grant needs a `refresh_token`")?;
+            vec![
+                ("grant_type", "refresh_token".into()),
+                ("refresh_token", refresh),
+            ]
+        }
+        OAuth2Grant::ClientCredentials => vec![("grant_type", "client_credentials".into())],
+    })
+}
+
+/// Posts `form`, completed with the scopes, audience and client credentials, to the token
+/// endpoint.
+async fn request_token(
+    client: &Client,
+    cfg: &OAuth2,
+    mut form: Vec<(&'static str, String)>,
+) -> Result<TokenResponse> {
+    if !cfg.scopes.is_empty() {
+        form.push(("scope", cfg.scopes.join(" ")));
+    }
+    if let Some(audience) = &cfg.audience {
+        form.push(("audience", audience.clone()));
+    }
+
+    let mut builder = client.post(&cfg.token_url);
+    match cfg.client_auth {
+        ClientAuth::Basic => {
+            builder = builder.basic_auth(&cfg.client_id, cfg.client_secret.as_ref());
+        }
+        ClientAuth::Body => {
+            form.push(("client_id", cfg.client_id.clone()));
+            if let Some(secret) = &cfg.client_secret {
+                form.push(("client_secret", secret.clone()));
+            }
+        }
+    }
+
+    let resp = builder
+        .form(&form)
+        .send()
+        .await
+        .with_context(|| format!("requesting OAuth2 token from {}", cfg.token_url))?;
+    let status = resp.status();
+    if !status.is_success() {
+        let body = resp.text().await.unwrap_or_default();
+        bail!("OAuth2 token endpoint answered {status}: {body}");
+    }
+    resp.json().await.context("parsing OAuth2 token response")
+}
@@ -0,0 +1,50 @@
+use anyhow::{anyhow, Result};
+use reqwest::{
+    header::{HeaderMap, LINK},
+    Url,
+};
+use serde_json::Value;
+
+/// The items of a page: the elements of the array at `path`, or every node it selects.
+pub fn page_items(body: &Value, path: &str) -> Result<Vec<Value>> {
+    let nodes = jsonpath_lib::select(body, path)
+        .map_err(|e| anyhow!("invalid items path '{path}': {e}"))?;
+    Ok(match nodes.as_slice() {
+        [Value::Array(items)] => items.clone(),
+        nodes => nodes.iter().map(|v| (*v).clone()).collect(),
+    })
+}
+
+/// The `rel="next"` target of the `Link` headers of the page at `url`, resolved against it.
+pub fn next_link(url: &Url, headers: &HeaderMap) -> Option<Url> {
+    headers
+        .get_all(LINK)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .find_map(|link| {
+            let (target, params) = link.trim().split_once(';')?;
+            let is_next = params.split(';').any(|p| {
+                p.trim()
+                    .strip_prefix("rel=")
+                    .is_some_and(|rel| rel.trim_matches('"').split(' ').any(|r| r == "next"))
+            });
+            let target = target.trim().strip_prefix('<')?.strip_suffix('>')?;
+            is_next.then(|| url.join(target).ok()).flatten()
+        })
+}
+
+/// `url` with `param` set to `value`, replacing any previous value.
+pub fn with_param(url: &Url, param: &str, value: &str) -> Url {
+    let mut url = url.clone();
+    let pairs: Vec<(String, String)> = url
+        .query_pairs()
+        .filter(|(k, _)| k != param)
+        .map(|(k, v)| (k.into_owned(), v.into_owned()))
+        .collect();
+    url.query_pairs_mut()
+        .clear()
+        .extend_pairs(pairs)
+        .append_pair(param, value);
+    url
+}
@@ -0,0 +1,268 @@
+use std::{
+    borrow::Cow,
+    io::{IsTerminal, Write},
+    process::{Command, Stdio},
+    sync::OnceLock,
+};
+use syntect::{
+    easy::HighlightLines,
+    highlighting::{Theme, ThemeSet},
+    parsing::SyntaxSet,
+    util::{as_24_bit_terminal_escaped, LinesWithEndings},
+};
+
+/// Body flavours that get their own pretty-printing and highlighting.
+#[derive(Clone, Copy, PartialEq)]
+enum Kind {
+    Json,
+    Xml,
+    Html,
+    Yaml,
+    Plain,
+}
+
+fn mime(content_type: Option<&str>) -> String {
+    content_type
+        .and_then(|ct| ct.split(';').next())
+        .unwrap_or("")
+        .trim()
+        .to_ascii_lowercase()
+}
+
+fn charset(content_type: Option<&str>) -> Option<&'static encoding_rs::Encoding> {
+    content_type?
+        .split(';')
+        .skip(1)
+        .filter_map(|param| param.split_once('='))
+        .find(|(k, _)| k.trim().eq_ignore_ascii_case("charset"))
+        .and_then(|(_, v)| encoding_rs::Encoding::for_label(v.trim().trim_matches('"').as_bytes()))
+}
+
+/// Images, archives, protobuf and the like, or anything that does not look like text.
+pub fn is_binary(bytes: &[u8], content_type: Option<&str>) -> bool {
+    let mime = mime(content_type);
+    let binary_type = ["image/", "audio/", "video/", "font/"]
+        .iter()
+        .any(|p| mime.starts_with(p))
+        || [
+            "application/octet-stream",
+            "application/pdf",
+            "application/zip",
+            "application/gzip",
+            "application/x-gzip",
+            "application/protobuf",
+            "application/x-protobuf",
+            "application/grpc",
+        ]
+        .iter()
+        .any(|t| mime.starts_with(t));
+    if binary_type {
+        return true;
+    }
+    if charset(content_type).is_some()
+        || mime.starts_with("text/")
+        || Kind::from_content_type(content_type) != Kind::Plain
+    {
+        return false;
+    }
+    bytes.contains(&0) || std::str::from_utf8(bytes).is_err()
+}
+
+/// Decodes a text body with the charset of its Content-Type, UTF-8 otherwise.
+pub fn decode<'a>(bytes: &'a [u8], content_type: Option<&str>) -> Cow<'a, str> {
+    match charset(content_type) {
+        Some(encoding) => encoding.decode(bytes).0,
+        None => String::from_utf8_lossy(bytes),
+    }
+}
+
+/// `xxd`-style dump of the first `limit` bytes.
+pub fn hex_dump(bytes: &[u8], limit: usize) -> String {
+    let mut out = String::new();
+    for (i, chunk) in bytes[..bytes.len().min(limit)].chunks(16).enumerate() {
+        let hex: Vec<String> = chunk.iter().map(|b| format!("{b:02x}")).collect();
+        let ascii: String = chunk
+            .iter()
+            .map(|&b| {
+                if b.is_ascii_graphic() || b == b' ' {
+                    b as char
+                } else {
+                    '.'
+                }
+            })
+            .collect();
+        out += &format!("{:08x}  {:<48} |{ascii}|\n", i * 16, hex.join(" "));
+    }
+    if bytes.len() > limit {
+        out += &format!("… {} more bytes\n", bytes.len() - limit);
+    }
+    out
+}
+
+impl Kind {
+    fn from_content_type(content_type: Option<&str>) -> Kind {
+        let mime = mime(content_type);
+        if mime == "application/json" || mime.ends_with("+json") {
+            Kind::Json
+        } else if mime.ends_with("/xml") || mime.ends_with("+xml") {
+            Kind::Xml
+        } else if mime == "text/html" {
+            Kind::Html
+        } else if mime.contains("yaml") {
+            Kind::Yaml
+        } else {
+            Kind::Plain
+        }
+    }
+
+    fn extension(self) -> Option<&'static str> {
+        match self {
+            Kind::Json => Some("json"),
+            Kind::Xml => Some("xml"),
+            Kind::Html => Some("html"),
+            Kind::Yaml => Some("yaml"),
+            Kind::Plain => None,
+        }
+    }
+}
+
+fn syntaxes() -> &'static SyntaxSet {
+    static SET: OnceLock<SyntaxSet> = OnceLock::new();
+    SET.get_or_init(SyntaxSet::load_defaults_newlines)
+}
+
+fn theme() -> &'static Theme {
+    static THEME: OnceLock<Theme> = OnceLock::new();
+    THEME.get_or_init(|| {
+        let mut themes = ThemeSet::load_defaults().themes;
+        themes
+            .remove("base16-ocean.dark")
+            .expect("bundled syntect theme")
+    })
+}
+
+pub fn content_type(headers: &[(String, String)]) -> Option<&str> {
+    headers
+        .iter()
+        .find(|(k, _)| k.eq_ignore_ascii_case("content-type"))
+        .map(|(_, v)| v.as_str())
+}
+
+/// Whether stdout is a terminal that should get colours.
+pub fn stdout_is_tty() -> bool {
+    std::io::stdout().is_terminal() && colored::control::SHOULD_COLORIZE.should_colorize()
+}
+
+/// Pretty-prints `body` according to its content type, cuts it after `max_body` bytes and,
+/// with `color`, highlights it for the terminal.
+pub fn render(
+    body: &str,
+    content_type: Option<&str>,
+    max_body: Option<usize>,
+    color: bool,
+) -> String {
+    let mut kind = Kind::from_content_type(content_type);
+    let parsed = match kind {
+        Kind::Json | Kind::Plain => serde_json::from_str::<serde_json::Value>(body).ok(),
+        _ => None,
+    };
+    let mut text = match parsed {
+        Some(v) => {
+            kind = Kind::Json;
+            serde_json::to_string_pretty(&v).unwrap_or_else(|_| body.to_string())
+        }
+        None => body.to_string(),
+    };
+
+    let mut note = None;
+    if let Some(max) = max_body.filter(|max| text.len() > *max) {
+        let cut = (0..=max)
+            .rev()
+            .find(|i| text.is_char_boundary(*i))
+            .unwrap_or(0);
+        note = Some(format!(
+            "… {} of {} bytes shown (raise --max-body or use --output)",
+            cut,
+            text.len()
+        ));
+        text.truncate(cut);
+    }
+
+    if color {
+        if let Some(syntax) = kind
+            .extension()
+            .and_then(|ext| syntaxes().find_syntax_by_extension(ext))
+        {
+            let mut lines = HighlightLines::new(syntax, theme());
+            let mut out = String::with_capacity(text.len() * 2);
+            for line in LinesWithEndings::from(&text) {
+                match lines.highlight_line(line, syntaxes()) {
+                    Ok(ranges) => out += &as_24_bit_terminal_escaped(&ranges, false),
+                    Err(_) => out += line,
+                }
+            }
+            text = out + "\x1b[0m";
+        }
+    }
+
+    match note {
+        Some(note) if color => format!("{text}\n\x1b[2m{note}\x1b[0m"),
+        Some(note) => format!("{text}\n{note}"),
+        None => text,
+    }
+}
+
+/// Bytes of a binary body shown in its hex preview.
+const HEX_PREVIEW: usize = 256;
+
+/// A response body for the terminal: `render`ed when it is text, a hex preview when binary.
+pub fn body(
+    bytes: &[u8],
+    content_type: Option<&str>,
+    max_body: Option<usize>,
+    color: bool,
+) -> String {
+    if is_binary(bytes, content_type) {
+        let kind = content_type.unwrap_or("unknown type");
+        let dump = hex_dump(bytes, max_body.unwrap_or(HEX_PREVIEW).min(HEX_PREVIEW));
+        let head = format!("<binary body: {} bytes, {kind}>", bytes.len());
+        return if color {
+            format!("\x1b[2m{head}\n{}\x1b[0m", dump.trim_end())
+        } else {
+            format!("{head}\n{}", dump.trim_end())
+        };
+    }
+    render(&decode(bytes, content_type), content_type, max_body, color)
+}
+
+/// Prints `text` on stdout, through `$PAGER` (default `less -R`) when `pager` is set and it
+/// does not fit the terminal.
+pub fn show(text: &str, pager: bool) {
+    let term = console::Term::stdout();
+    let rows = term.size_checked().map(|(rows, _)| rows as usize);
+    let too_long = rows.is_some_and(|rows| text.lines().count() + 2 > rows);
+    if pager && too_long && std::io::stdout().is_terminal() && page(text) {
+        return;
+    }
+    println!("{text}\n");
+}
+
+fn page(text: &str) -> bool {
+    let cmd = std::env::var("PAGER").unwrap_or_else(|_| "less -R".into());
+    let mut parts = cmd.split_whitespace();
+    let Some(program) = parts.next() else {
+        return false;
+    };
+    let Ok(mut child) = Command::new(program)
+        .args(parts)
+        .stdin(Stdio::piped())
+        .spawn()
+    else {
+        return false;
+    };
+    if let Some(mut stdin) = child.stdin.take() {
+        // The pager closing early (`q`) is not an error.
+        let _ = stdin.write_all(text.as_bytes());
+    }
+    child.wait().is_ok()
+}
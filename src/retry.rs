@@ -0,0 +1,66 @@
+use crate::loader::{Retry, RetryError};
+use reqwest::{
+    header::{HeaderMap, RETRY_AFTER},
+    Method, StatusCode,
+};
+use std::time::Duration;
+
+/// Longest `Retry-After` honored, so a server asking for a day doesn't silently stall the run.
+pub const MAX_RETRY_AFTER: Duration = Duration::from_secs(60);
+
+/// Number of attempts `policy` allows for a request sent with `method`.
+pub fn max_attempts(policy: Option<&Retry>, method: &Method) -> u32 {
+    match policy {
+        Some(p) if !p.idempotent_only || is_idempotent(method) => p.max_attempts.max(1),
+        _ => 1,
+    }
+}
+
+fn is_idempotent(method: &Method) -> bool {
+    matches!(
+        *method,
+        Method::GET | Method::HEAD | Method::OPTIONS | Method::PUT | Method::DELETE | Method::TRACE
+    )
+}
+
+pub fn retries_status(policy: &Retry, status: StatusCode) -> bool {
+    policy.on_status.contains(&status.as_u16())
+}
+
+pub fn retries_error(policy: &Retry, err: &reqwest::Error) -> bool {
+    policy.on_errors.iter().any(|kind| match kind {
+        RetryError::Timeout => err.is_timeout(),
+        RetryError::Connect => err.is_connect(),
+    })
+}
+
+/// Exponential backoff: `backoff_ms * 2^(attempt - 1)` plus up to `jitter_ms` of random delay.
+pub fn backoff(policy: &Retry, attempt: u32) -> Duration {
+    let base = policy
+        .backoff_ms
+        .saturating_mul(1u64 << (attempt - 1).min(16));
+    let jitter = if policy.jitter_ms > 0 {
+        fastrand::u64(0..=policy.jitter_ms)
+    } else {
+        0
+    };
+    Duration::from_millis(base.saturating_add(jitter))
+}
+
+/// Delay requested by a `Retry-After` header on 429/503 responses, either in seconds or as an HTTP date,
+/// capped at `MAX_RETRY_AFTER`.
+pub fn retry_after(status: StatusCode, headers: &HeaderMap) -> Option<Duration> {
+    if status != StatusCode::TOO_MANY_REQUESTS && status != StatusCode::SERVICE_UNAVAILABLE {
+        return None;
+    }
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+    let wait = match value.parse::<u64>() {
+        Ok(secs) => Duration::from_secs(secs),
+        Err(_) => {
+            let at = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+            let wait = at.signed_duration_since(chrono::Utc::now());
+            wait.to_std().unwrap_or(Duration::ZERO)
+        }
+    };
+    Some(wait.min(MAX_RETRY_AFTER))
+}
@@ -1,68 +1,190 @@
-use crate::loader::{Header, Request};
-use anyhow::{Context, Result};
+use crate::loader::{
+    Api, Auth, BodyType, Header, PageStyle, Paginate, Part, Proxy, Request, Retry, Tls,
+};
+use anyhow::{anyhow, bail, Context, Result};
 use colored::Colorize;
-use reqwest::cookie::Jar;
-use reqwest::{header::HeaderName, redirect, Client, Method};
-use std::{collections::HashMap, sync::Arc};
+use indicatif::{ProgressBar, ProgressStyle};
+use reqwest::{
+    header::{HeaderMap, HeaderName, HeaderValue, AUTHORIZATION, CONTENT_TYPE, WWW_AUTHENTICATE},
+    multipart, redirect, Certificate, Client, Identity, Method, NoProxy, RequestBuilder, Response,
+    StatusCode, Version,
+};
+use reqwest_cookie_store::CookieStoreMutex;
+use serde_json::Value;
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    fs::{self, File},
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
-fn pretty_json(s: &str) -> String {
-    serde_json::from_str::<serde_json::Value>(s)
-        .map(|v| serde_json::to_string_pretty(&v).unwrap_or_else(|_| s.to_string()))
-        .unwrap_or_else(|_| s.to_string())
+pub enum Tests {
+    Skipped,
+    Passed,
+    Failed(anyhow::Error),
 }
 
-pub async fn run_single_request(
-    base_url: &str,
-    project: &str,
-    env: &str,
-    request: &Request,
-) -> Result<()> {
-    let jar = Arc::new(Jar::default());
-    let client = Client::builder()
-        .user_agent("qwest/0.2 (rust-cli-http)")
-        .cookie_provider(jar)
-        .redirect(redirect::Policy::limited(10))
-        .build()
-        .context("building reqwest client")?;
+pub struct Outcome {
+    pub status: StatusCode,
+    pub headers: Vec<(String, String)>,
+    /// `None` when the body was saved to a file.
+    pub body: Option<Vec<u8>>,
+    pub timing: Timing,
+    pub tests: Tests,
+}
 
-    execute(&client, base_url, project, env, request).await?;
-    Ok(())
+/// Where the time of a spell went, plus the size of both bodies.
+///
+/// reqwest does not report connect or TLS handshake times, so they are part of `ttfb`.
+#[derive(Clone, Copy, Default)]
+pub struct Timing {
+    /// From sending the first attempt to having read the whole body, retries included.
+    pub total: Duration,
+    /// From sending the final attempt to receiving its response headers.
+    pub ttfb: Duration,
+    /// Reading (or saving) the response body.
+    pub download: Duration,
+    /// `None` when the body is streamed (multipart) and its size is unknown up front.
+    pub request_bytes: Option<u64>,
+    /// Decoded size of the response body.
+    pub response_bytes: u64,
 }
 
-async fn execute(
-    client: &Client,
-    base_url: &str,
-    project: &str,
-    env: &str,
-    req: &Request,
-) -> Result<()> {
-    let mut vars = crate::db::load_vars(project, env).unwrap_or_default();
+impl Timing {
+    /// The `timing` map handed to test scripts.
+    fn to_json(self) -> serde_json::Value {
+        serde_json::json!({
+            "total_ms": self.total.as_millis() as u64,
+            "ttfb_ms": self.ttfb.as_millis() as u64,
+            "download_ms": self.download.as_millis() as u64,
+            "request_bytes": self.request_bytes,
+            "response_bytes": self.response_bytes,
+        })
+    }
+}
 
-    if let Some(code) = &req.pre_script {
-        let mut senv = crate::script::ScriptEnv {
-            vars: &mut vars,
-            status: None,
-            headers: None,
-            data: None,
-            project: project.to_string(),
-            env: env.to_string(),
-        };
-        crate::script::run_script(code, &mut senv)?;
+impl std::fmt::Display for Timing {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "⏱ total {} ms · ttfb {} ms · download {} ms",
+            self.total.as_millis(),
+            self.ttfb.as_millis(),
+            self.download.as_millis()
+        )?;
+        if let Some(sent) = self.request_bytes {
+            write!(f, " · sent {sent} B")?;
+        }
+        write!(f, " · received {} B", self.response_bytes)
+    }
+}
+
+impl Outcome {
+    /// A spell passes when its tests pass, or when it has none and the status is not 4xx/5xx.
+    pub fn passed(&self) -> bool {
+        match self.tests {
+            Tests::Passed => true,
+            Tests::Failed(_) => false,
+            Tests::Skipped => !(self.status.is_client_error() || self.status.is_server_error()),
+        }
     }
+}
 
-    let url = format!("{}{}", base_url, req.path);
-    println!("{}", format!("→ {} {}", req.method, url).bold());
+/// Per-invocation options coming from the command line rather than the spell-book.
+#[derive(Clone, Default)]
+pub struct RunOptions {
+    /// Streams the body to this file instead of printing it; overrides the spell's `save_to`.
+    pub output: Option<PathBuf>,
+    /// Prints the timing breakdown after the body.
+    pub timing: bool,
+    /// Cuts the printed body after this many bytes.
+    pub max_body: Option<usize>,
+    /// Pages bodies longer than the terminal through `$PAGER`.
+    pub pager: bool,
+    pub format: OutputFormat,
+    /// Overrides `max_events` of the spell's `[request.sse]`.
+    pub max_events: Option<u64>,
+    /// Overrides `timeout_ms` of the spell's `[request.sse]`.
+    pub stream_timeout: Option<Duration>,
+    /// The caller's spinner, held back while the body is shown so it doesn't draw over the pager.
+    pub spinner: Option<ProgressBar>,
+}
+
+/// What `qwest run` writes to stdout. Anything else goes to stderr in the machine formats.
+#[derive(Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum OutputFormat {
+    /// The coloured `→`/`←` transcript with headers and a prettified body.
+    #[default]
+    Transcript,
+    /// One JSON document with status, headers, body, timing and test result.
+    Json,
+    /// The response body as received.
+    Body,
+    /// The status line and response headers.
+    Headers,
+    /// Status line, headers and body as received, like `curl -i`.
+    Raw,
+    /// The transcript on stderr, for dependencies of a spell run in a machine format.
+    #[value(skip)]
+    Stderr,
+}
+
+/// `println!` for the transcript: stdout, or stderr when stdout carries a machine format.
+macro_rules! say {
+    ($opts:expr, $($arg:tt)*) => {
+        if $opts.format == OutputFormat::Transcript {
+            println!($($arg)*)
+        } else {
+            eprintln!($($arg)*)
+        }
+    };
+}
 
+/// A spell resolved into a client and request template, ready to be sent any number of times.
+pub struct Prepared {
+    method: Method,
+    client: Client,
+    transport: Transport,
+    builder: RequestBuilder,
+    content_type: Option<String>,
+    auth: Option<Auth>,
+    req: Request,
+}
+
+/// Resolves headers, params and credentials (fetching an OAuth2 token if needed) once.
+pub async fn prepare(
+    session: &Session,
+    api: &Api,
+    project: &str,
+    env: &str,
+    req: &Request,
+) -> Result<Prepared> {
+    let url = format!("{}{}", api.base_url, req.path);
     let method =
         Method::from_bytes(req.method.as_bytes()).context("invalid HTTP method in config")?;
-    let mut builder = client.request(method, &url);
+    let transport = session.transport.with_request(req);
+    let client = session.client_for(&transport)?.into_owned();
+    let mut builder = client.request(method.clone(), &url);
+    // An event stream is meant to stay open; `[request.sse]` has its own limits.
+    if let (Some(timeout), None) = (transport.timeout, &req.sse) {
+        builder = builder.timeout(timeout);
+    }
+    if req.sse.is_some()
+        && !req
+            .headers
+            .iter()
+            .any(|h| h.key.eq_ignore_ascii_case("accept"))
+    {
+        builder = builder.header(reqwest::header::ACCEPT, "text/event-stream");
+    }
 
-    let mut content_type_form = false;
+    let mut content_type = None;
     for Header { key, value } in &req.headers {
-        if key.eq_ignore_ascii_case("content-type")
-            && value.eq_ignore_ascii_case("application/x-www-form-urlencoded")
-        {
-            content_type_form = true;
+        if key.eq_ignore_ascii_case("content-type") {
+            content_type = Some(value.clone());
         }
         builder = builder.header(HeaderName::from_bytes(key.as_bytes())?, value);
     }
@@ -71,33 +193,116 @@ async fn execute(
         builder = builder.query(&params.as_object().unwrap_or(&serde_json::Map::new()));
     }
 
-    if let Some(body) = &req.body {
-        if content_type_form {
-            let obj = body.as_object().context("form body must be JSON object")?;
-            let form: HashMap<String, String> = obj
-                .iter()
-                .map(|(k, v)| (k.clone(), v.as_str().unwrap_or("").to_owned()))
-                .collect();
-            builder = builder.form(&form);
-        } else {
-            builder = builder.json(body);
+    let auth = req.auth.as_ref().or(api.auth.as_ref());
+    if let Some(auth) = auth {
+        builder = crate::auth::apply(builder, auth);
+    }
+    if let Some(Auth::OAuth2(oauth)) = auth {
+        let token = crate::oauth::access_token(&session.client, project, env, oauth).await?;
+        builder = builder.bearer_auth(token);
+    }
+
+    Ok(Prepared {
+        method,
+        client,
+        transport,
+        builder,
+        content_type,
+        auth: auth.cloned(),
+        req: req.clone(),
+    })
+}
+
+impl Prepared {
+    /// A fresh request with its body attached and, when configured, signed.
+    pub fn build(&self, digest: Option<&HeaderValue>) -> Result<reqwest::Request> {
+        self.build_to(None, digest)
+    }
+
+    /// Like `build`, but sent to `url` instead of the spell's own URL.
+    fn build_to(
+        &self,
+        url: Option<reqwest::Url>,
+        digest: Option<&HeaderValue>,
+    ) -> Result<reqwest::Request> {
+        let builder = self
+            .builder
+            .try_clone()
+            .context("request cannot be replayed")?;
+        let mut request = attach_body(builder, &self.req, self.content_type.as_deref())?
+            .build()
+            .context("building request")?;
+        if let Some(url) = url {
+            *request.url_mut() = url;
+        }
+        if let Some(value) = digest {
+            request.headers_mut().insert(AUTHORIZATION, value.clone());
+        }
+        if let Some(Auth::AwsSigv4(aws)) = &self.auth {
+            crate::sigv4::sign(&mut request, aws)?;
         }
+        Ok(request)
     }
 
-    // Send
-    let resp = builder.send().await.context("HTTP send failed")?;
-    let status = resp.status();
-    let headers_map: HashMap<String, String> = resp
-        .headers()
+    pub fn client(&self) -> &Client {
+        &self.client
+    }
+
+    /// The `Authorization` answering `resp`'s Digest challenge, when the spell uses Digest auth
+    /// and `resp` is a 401 carrying one.
+    pub fn digest_answer(
+        &self,
+        resp: &Response,
+        method: &Method,
+        url: &reqwest::Url,
+    ) -> Result<Option<HeaderValue>> {
+        let Some(Auth::Digest { username, password }) = &self.auth else {
+            return Ok(None);
+        };
+        if resp.status() != StatusCode::UNAUTHORIZED {
+            return Ok(None);
+        }
+        let Some(challenge) = resp
+            .headers()
+            .get_all(WWW_AUTHENTICATE)
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .find(|v| v.trim_start().starts_with("Digest"))
+        else {
+            return Ok(None);
+        };
+        let answer = crate::auth::digest_authorization(challenge, method, url, username, password)?;
+        Ok(Some(HeaderValue::from_str(&answer)?))
+    }
+}
+
+/// The request as it went over the wire on the final attempt.
+struct Sent {
+    method: Method,
+    url: reqwest::Url,
+    headers: Vec<(String, String)>,
+    /// `None` when the body was streamed and its bytes are unknown.
+    body: Option<Vec<u8>>,
+}
+
+pub(crate) fn header_pairs(headers: &HeaderMap) -> Vec<(String, String)> {
+    headers
         .iter()
-        .map(|(k, v)| (k.to_string(), v.to_str().unwrap_or("").to_string()))
-        .collect();
+        .map(|(k, v)| {
+            (
+                k.to_string(),
+                String::from_utf8_lossy(v.as_bytes()).into_owned(),
+            )
+        })
+        .collect()
+}
 
-    let text = resp
-        .text()
-        .await
-        .unwrap_or_else(|_| "<non-utf8 body>".into());
+/// Prints the `← 200 OK` line, coloured by status class, then the headers.
+pub(crate) fn print_head(status: StatusCode, headers: &[(String, String)]) {
+    print!("{}", head(status, headers));
+}
 
+fn head(status: StatusCode, headers: &[(String, String)]) -> String {
     let status_str = status.as_u16().to_string();
     let colored_status = if status.is_success() {
         status_str.green().bold()
@@ -108,33 +313,1004 @@ async fn execute(
     } else {
         status_str.normal()
     };
-    println!(
-        "{} {} {}",
+    let mut out = format!(
+        "{} {} {}\n",
         "←".bold(),
         colored_status,
         status.canonical_reason().unwrap_or("")
     );
+    for (k, v) in headers {
+        out += &format!("{}: {}\n", k.dimmed(), v);
+    }
+    out + "\n"
+}
+
+/// One page of a paginated listing.
+struct Page {
+    url: reqwest::Url,
+    headers: HeaderMap,
+    body: Value,
+}
+
+/// Fetches the pages after `first` and returns the items of all of them.
+async fn follow_pages(
+    prepared: &Prepared,
+    paginate: &Paginate,
+    first: Page,
+    opts: &RunOptions,
+) -> Result<Value> {
+    let mut items = crate::paginate::page_items(&first.body, &paginate.items)?;
+    let origin = first.url.clone();
+    let mut page = first;
+    let mut pages = 1;
+    let mut last_count = items.len();
+    while pages < paginate.max_pages && last_count > 0 {
+        let next = match &paginate.style {
+            PageStyle::Link => crate::paginate::next_link(&page.url, &page.headers),
+            PageStyle::Cursor { cursor, param } => {
+                let found = jsonpath_lib::select(&page.body, cursor)
+                    .map_err(|e| anyhow!("invalid cursor path '{cursor}': {e}"))?;
+                match found.first() {
+                    Some(Value::String(c)) if !c.is_empty() => {
+                        Some(crate::paginate::with_param(&origin, param, c))
+                    }
+                    Some(Value::Number(n)) => {
+                        Some(crate::paginate::with_param(&origin, param, &n.to_string()))
+                    }
+                    _ => None,
+                }
+            }
+            PageStyle::Page { param, start } => Some(crate::paginate::with_param(
+                &origin,
+                param,
+                &(start + pages as u64).to_string(),
+            )),
+            PageStyle::Offset { param, start } => Some(crate::paginate::with_param(
+                &origin,
+                param,
+                &(start + items.len() as u64).to_string(),
+            )),
+        };
+        let Some(url) = next else { break };
+
+        // A Digest answer is bound to its URI, so each page answers its own challenge.
+        let mut resp = prepared
+            .client
+            .execute(prepared.build_to(Some(url.clone()), None)?)
+            .await
+            .map_err(|e| send_error(e, url.as_str(), &prepared.transport))?;
+        if let Some(answer) = prepared.digest_answer(&resp, &prepared.method, &url)? {
+            resp = prepared
+                .client
+                .execute(prepared.build_to(Some(url.clone()), Some(&answer))?)
+                .await
+                .map_err(|e| send_error(e, url.as_str(), &prepared.transport))?;
+        }
+        pages += 1;
+        if !resp.status().is_success() {
+            bail!("page {pages} ({url}) returned {}", resp.status());
+        }
+        let headers = resp.headers().clone();
+        let bytes = resp.bytes().await.context("reading response body")?;
+        let body: Value = serde_json::from_slice(&bytes)
+            .with_context(|| format!("page {pages} ({url}) is not JSON"))?;
+        let found = crate::paginate::page_items(&body, &paginate.items)?;
+        say!(
+            opts,
+            "{}",
+            format!("↳ page {pages}: {} items", found.len()).dimmed()
+        );
+        last_count = found.len();
+        items.extend(found);
+        page = Page { url, headers, body };
+    }
+    say!(
+        opts,
+        "{}\n",
+        format!("⇶ {} items from {pages} pages", items.len()).bold()
+    );
+    Ok(Value::Array(items))
+}
+
+/// Prints events as they arrive and runs the spell's `on_event` script on each, until the
+/// stream ends or hits the event count or time limit. Returns the raw stream and the events.
+async fn stream_events(
+    mut resp: Response,
+    req: &Request,
+    vars: &mut HashMap<String, String>,
+    project: &str,
+    env: &str,
+    opts: &RunOptions,
+) -> Result<(Vec<u8>, Vec<Value>)> {
+    let sse = req.sse.clone().unwrap_or_default();
+    let max_events = opts.max_events.or(sse.max_events);
+    let limit = opts
+        .stream_timeout
+        .or(sse.timeout_ms.map(Duration::from_millis));
+    let deadline = limit.map(|d| tokio::time::Instant::now() + d);
+    let color = crate::render::stdout_is_tty();
+
+    let mut parser = crate::sse::Parser::default();
+    let mut raw = Vec::new();
+    let mut events = Vec::new();
+    'stream: loop {
+        let chunk = match deadline {
+            Some(deadline) => match tokio::time::timeout_at(deadline, resp.chunk()).await {
+                Ok(chunk) => chunk,
+                Err(_) => {
+                    let secs = limit.unwrap_or_default().as_secs_f64();
+                    say!(
+                        opts,
+                        "{}",
+                        format!("⏹ stopped listening after {secs} s").dimmed()
+                    );
+                    break;
+                }
+            },
+            None => resp.chunk().await,
+        };
+        let chunk = match chunk {
+            Ok(Some(chunk)) => chunk,
+            Ok(None) => break,
+            Err(e) if e.is_timeout() => {
+                say!(opts, "{}", "⏹ stream timed out".dimmed());
+                break;
+            }
+            Err(e) => return Err(anyhow!(e).context("reading event stream")),
+        };
+        raw.extend_from_slice(&chunk);
+        if matches!(opts.format, OutputFormat::Body | OutputFormat::Raw) {
+            let mut stdout = std::io::stdout().lock();
+            stdout.write_all(&chunk)?;
+            stdout.flush()?;
+        }
+
+        for event in parser.feed(&chunk) {
+            let data = event.data_json();
+            let title = match &event.id {
+                Some(id) => format!("▸ {} #{id}", event.event),
+                None => format!("▸ {}", event.event),
+            };
+            let ct = data.is_object().then_some("application/json");
+            match opts.format {
+                OutputFormat::Transcript => println!(
+                    "{}\n{}\n",
+                    title.cyan().bold(),
+                    crate::render::render(&event.data, ct, opts.max_body, color)
+                ),
+                OutputFormat::Stderr => eprintln!(
+                    "{title}\n{}\n",
+                    crate::render::render(&event.data, ct, opts.max_body, false)
+                ),
+                _ => {}
+            }
+
+            let value = event.to_json();
+            if let Some(code) = &sse.on_event {
+                let mut senv = crate::script::ScriptEnv {
+                    vars: &mut *vars,
+                    status: None,
+                    headers: None,
+                    data: Some(data),
+                    body: None,
+                    attempts: None,
+                    polls: None,
+                    timing: None,
+                    event: Some(value.clone()),
+                    project: project.to_string(),
+                    env: env.to_string(),
+                };
+                crate::script::run_script(code, &mut senv).with_context(|| {
+                    format!("on_event script failed on event {}", events.len() + 1)
+                })?;
+            }
+            events.push(value);
+            if max_events.is_some_and(|max| events.len() as u64 >= max) {
+                say!(
+                    opts,
+                    "{}",
+                    format!("⏹ stopped after {} events", events.len()).dimmed()
+                );
+                break 'stream;
+            }
+        }
+    }
+    Ok((raw, events))
+}
+
+/// A response whose body is still to be read, or was already read to check `until`.
+enum Received {
+    Pending(Response),
+    Read(Vec<u8>),
+}
+
+fn header_map(headers: &HeaderMap) -> HashMap<String, String> {
+    headers
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.to_str().unwrap_or("").to_string()))
+        .collect()
+}
+
+/// The head as it came over the wire (`HTTP/1.1 200 OK` and headers), uncoloured.
+fn raw_head(version: Version, status: StatusCode, headers: &[(String, String)]) -> String {
+    let mut out = format!(
+        "{:?} {} {}\n",
+        version,
+        status.as_u16(),
+        status.canonical_reason().unwrap_or("")
+    );
+    for (k, v) in headers {
+        out += &format!("{k}: {v}\n");
+    }
+    out + "\n"
+}
+
+const DEFAULT_MAX_REDIRECTS: usize = 10;
+
+/// Transport settings of the `[api]` section, with a spell's own overrides applied on top.
+#[derive(Clone, Copy)]
+struct Transport {
+    timeout: Option<Duration>,
+    connect_timeout: Option<Duration>,
+    follow_redirects: bool,
+    max_redirects: usize,
+}
+
+impl Transport {
+    fn from_api(api: &Api) -> Self {
+        Transport {
+            timeout: api.timeout_ms.map(Duration::from_millis),
+            connect_timeout: api.connect_timeout_ms.map(Duration::from_millis),
+            follow_redirects: api.follow_redirects.unwrap_or(true),
+            max_redirects: api.max_redirects.unwrap_or(DEFAULT_MAX_REDIRECTS),
+        }
+    }
+
+    fn with_request(self, req: &Request) -> Self {
+        Transport {
+            timeout: req.timeout_ms.map(Duration::from_millis).or(self.timeout),
+            connect_timeout: req
+                .connect_timeout_ms
+                .map(Duration::from_millis)
+                .or(self.connect_timeout),
+            follow_redirects: req.follow_redirects.unwrap_or(self.follow_redirects),
+            max_redirects: req.max_redirects.unwrap_or(self.max_redirects),
+        }
+    }
+
+    fn redirect_policy(&self) -> redirect::Policy {
+        if self.follow_redirects {
+            redirect::Policy::limited(self.max_redirects)
+        } else {
+            redirect::Policy::none()
+        }
+    }
+
+    /// Whether a client built for `other` can serve requests with these settings.
+    fn shares_client_with(&self, other: &Transport) -> bool {
+        self.connect_timeout == other.connect_timeout
+            && self.follow_redirects == other.follow_redirects
+            && (!self.follow_redirects || self.max_redirects == other.max_redirects)
+    }
+}
+
+/// A client shared by every spell cast in one run, backed by the cookie jar persisted for
+/// the project and env.
+pub struct Session {
+    project: String,
+    env: String,
+    jar: Arc<CookieStoreMutex>,
+    transport: Transport,
+    tls: Tls,
+    proxy: Option<Proxy>,
+    retry: Option<Retry>,
+    client: Client,
+}
+
+impl Session {
+    pub fn new(api: &Api, project: &str, env: &str) -> Result<Self> {
+        let store = crate::cookies::load_store(project, env)?;
+        let jar = Arc::new(CookieStoreMutex::new(store));
+        let transport = Transport::from_api(api);
+        if api.tls.insecure {
+            eprintln!(
+                "{}",
+                "⚠ TLS certificate verification is disabled".yellow().bold()
+            );
+        }
+        let client = build_client(&jar, &transport, &api.tls, api.proxy.as_ref())?;
+        Ok(Session {
+            project: project.to_string(),
+            env: env.to_string(),
+            jar,
+            transport,
+            tls: api.tls.clone(),
+            proxy: api.proxy.clone(),
+            retry: api.retry.clone(),
+            client,
+        })
+    }
+
+    fn save_cookies(&self) -> Result<()> {
+        let store = self
+            .jar
+            .lock()
+            .map_err(|_| anyhow!("cookie jar lock poisoned"))?;
+        crate::cookies::save_store(&self.project, &self.env, &store)
+    }
+
+    /// Redirects and connect timeouts are client-wide in reqwest, so a spell overriding
+    /// them gets its own client sharing the session's cookie jar.
+    fn client_for(&self, transport: &Transport) -> Result<Cow<'_, Client>> {
+        if transport.shares_client_with(&self.transport) {
+            Ok(Cow::Borrowed(&self.client))
+        } else {
+            Ok(Cow::Owned(build_client(
+                &self.jar,
+                transport,
+                &self.tls,
+                self.proxy.as_ref(),
+            )?))
+        }
+    }
+}
+
+fn build_client(
+    jar: &Arc<CookieStoreMutex>,
+    transport: &Transport,
+    tls: &Tls,
+    proxy: Option<&Proxy>,
+) -> Result<Client> {
+    let mut builder = Client::builder()
+        .user_agent("qwest/0.2 (rust-cli-http)")
+        .cookie_provider(jar.clone())
+        .redirect(transport.redirect_policy());
+    if let Some(connect_timeout) = transport.connect_timeout {
+        builder = builder.connect_timeout(connect_timeout);
+    }
+
+    for path in &tls.ca_files {
+        let pem = fs::read(path).with_context(|| format!("reading CA bundle '{path}'"))?;
+        for cert in Certificate::from_pem_bundle(&pem)
+            .with_context(|| format!("parsing CA bundle '{path}'"))?
+        {
+            builder = builder.add_root_certificate(cert);
+        }
+    }
+    if let Some(cert_path) = &tls.client_cert {
+        let mut pem =
+            fs::read(cert_path).with_context(|| format!("reading client cert '{cert_path}'"))?;
+        if let Some(key_path) = &tls.client_key {
+            pem.push(b'\n');
+            pem.extend(
+                fs::read(key_path).with_context(|| format!("reading client key '{key_path}'"))?,
+            );
+        }
+        // PEM identities are only supported by the rustls backend.
+        builder = builder
+            .use_rustls_tls()
+            .identity(Identity::from_pem(&pem).context("loading client identity")?);
+    }
+    if tls.insecure {
+        builder = builder.danger_accept_invalid_certs(true);
+    }
+
+    if let Some(p) = proxy {
+        let no_proxy = NoProxy::from_string(&p.no_proxy.join(","));
+        let proxies = [
+            p.http.as_ref().map(|u| (u, reqwest::Proxy::http(u))),
+            p.https.as_ref().map(|u| (u, reqwest::Proxy::https(u))),
+            p.url.as_ref().map(|u| (u, reqwest::Proxy::all(u))),
+        ];
+        for (url, proxy) in proxies.into_iter().flatten() {
+            let proxy = proxy.with_context(|| format!("invalid proxy URL '{url}'"))?;
+            builder = builder.proxy(proxy.no_proxy(no_proxy.clone()));
+        }
+    }
+
+    builder.build().context("building reqwest client")
+}
+
+fn send_error(e: reqwest::Error, url: &str, transport: &Transport) -> anyhow::Error {
+    if !e.is_timeout() {
+        return anyhow!(e).context("HTTP send failed");
+    }
+    let msg = match (e.is_connect(), transport.connect_timeout, transport.timeout) {
+        (true, Some(t), _) => format!("connecting to {url} timed out after {} ms", t.as_millis()),
+        (_, _, Some(t)) => format!("request to {url} timed out after {} ms", t.as_millis()),
+        _ => format!("request to {url} timed out"),
+    };
+    anyhow!(e).context(msg)
+}
 
-    for (k, v) in &headers_map {
-        println!("{}: {}", k.dimmed(), v);
+/// Attaches the spell's body; called once per attempt since multipart bodies cannot be cloned.
+/// `content_type` is the Content-Type header set by the spell, if any.
+fn attach_body(
+    builder: RequestBuilder,
+    req: &Request,
+    content_type: Option<&str>,
+) -> Result<RequestBuilder> {
+    if !req.parts.is_empty() {
+        return Ok(builder.multipart(multipart_form(&req.parts)?));
     }
-    println!();
-    println!("{}\n", pretty_json(&text));
 
+    let default_type = match req.body_type {
+        Some(BodyType::Json) => "application/json".to_string(),
+        Some(BodyType::Text) => "text/plain; charset=utf-8".to_string(),
+        Some(BodyType::Xml) => "application/xml".to_string(),
+        Some(BodyType::Binary) => "application/octet-stream".to_string(),
+        None => match &req.body_file {
+            Some(path) => mime_guess::from_path(path)
+                .first_or_octet_stream()
+                .to_string(),
+            None => "application/json".to_string(),
+        },
+    };
+    let verbatim = |builder: RequestBuilder, bytes: Vec<u8>| match content_type {
+        Some(_) => builder.body(bytes),
+        None => builder.header(CONTENT_TYPE, &default_type).body(bytes),
+    };
+
+    if let Some(path) = &req.body_file {
+        let bytes = fs::read(path).with_context(|| format!("reading body file '{path}'"))?;
+        return Ok(verbatim(builder, bytes));
+    }
+    let Some(raw) = &req.body else {
+        return Ok(builder);
+    };
+
+    let is_form =
+        content_type.is_some_and(|ct| ct.eq_ignore_ascii_case("application/x-www-form-urlencoded"));
+    match req.json_body()? {
+        Some(body) if is_form => {
+            let obj = body.as_object().context("form body must be JSON object")?;
+            let form = obj
+                .iter()
+                .map(|(k, v)| {
+                    let v = match v {
+                        Value::String(s) => s.clone(),
+                        Value::Null => String::new(),
+                        Value::Bool(_) | Value::Number(_) => v.to_string(),
+                        _ => bail!("form field '{k}' must be a string, number or boolean"),
+                    };
+                    Ok((k.clone(), v))
+                })
+                .collect::<Result<HashMap<String, String>>>()?;
+            Ok(builder.form(&form))
+        }
+        Some(body) => Ok(builder.json(&body)),
+        None => Ok(verbatim(builder, raw.clone().into_bytes())),
+    }
+}
+
+fn multipart_form(parts: &[Part]) -> Result<multipart::Form> {
+    let mut form = multipart::Form::new();
+    for part in parts {
+        let mut p = match (&part.value, &part.file) {
+            (Some(value), None) => multipart::Part::text(value.clone()),
+            (None, Some(path)) => {
+                let bytes =
+                    fs::read(path).with_context(|| format!("reading file part '{path}'"))?;
+                let filename = part.filename.clone().unwrap_or_else(|| {
+                    Path::new(path)
+                        .file_name()
+                        .map(|n| n.to_string_lossy().into_owned())
+                        .unwrap_or_else(|| path.clone())
+                });
+                let mime = mime_guess::from_path(path).first_or_octet_stream();
+                multipart::Part::bytes(bytes)
+                    .file_name(filename)
+                    .mime_str(mime.as_ref())?
+            }
+            _ => bail!(
+                "part '{}' needs exactly one of `value` or `file`",
+                part.name
+            ),
+        };
+        if let Some(filename) = &part.filename {
+            p = p.file_name(filename.clone());
+        }
+        if let Some(content_type) = &part.content_type {
+            p = p
+                .mime_str(content_type)
+                .with_context(|| format!("invalid content_type for part '{}'", part.name))?;
+        }
+        form = form.part(part.name.clone(), p);
+    }
+    Ok(form)
+}
+
+/// Streams the response body to `path`, with a progress bar sized from Content-Length.
+async fn save_body(mut resp: Response, path: &Path) -> Result<u64> {
+    let pb = match resp.content_length() {
+        Some(len) => {
+            let pb = ProgressBar::new(len);
+            pb.set_style(
+                ProgressStyle::with_template(
+                    "{bar:40.green/dim} {bytes}/{total_bytes} ({bytes_per_sec}, {eta})",
+                )
+                .unwrap(),
+            );
+            pb
+        }
+        None => {
+            let pb = ProgressBar::new_spinner();
+            pb.set_style(
+                ProgressStyle::with_template("{spinner:.green} {bytes} ({bytes_per_sec})").unwrap(),
+            );
+            pb
+        }
+    };
+
+    let file = File::create(path).with_context(|| format!("creating {}", path.display()))?;
+    let mut out = BufWriter::new(file);
+    let mut written = 0u64;
+    while let Some(chunk) = resp.chunk().await.context("reading response body")? {
+        out.write_all(&chunk)
+            .with_context(|| format!("writing {}", path.display()))?;
+        written += chunk.len() as u64;
+        pb.inc(chunk.len() as u64);
+    }
+    out.flush()?;
+    pb.finish_and_clear();
+    Ok(written)
+}
+
+pub async fn execute(
+    session: &Session,
+    api: &Api,
+    project: &str,
+    env: &str,
+    vars: &mut HashMap<String, String>,
+    req: &Request,
+    opts: &RunOptions,
+) -> Result<Outcome> {
+    if let Some(code) = &req.pre_script {
+        let mut senv = crate::script::ScriptEnv {
+            vars,
+            status: None,
+            headers: None,
+            data: None,
+            body: None,
+            attempts: None,
+            polls: None,
+            timing: None,
+            event: None,
+            project: project.to_string(),
+            env: env.to_string(),
+        };
+        crate::script::run_script(code, &mut senv)?;
+    }
+
+    let url = format!("{}{}", api.base_url, req.path);
+    say!(opts, "{}", format!("→ {} {}", req.method, url).bold());
+
+    let prepared = prepare(session, api, project, env, req).await?;
+    let (client, transport) = (&prepared.client, &prepared.transport);
+    let retry = req.retry.as_ref().or(session.retry.as_ref());
+    let max_attempts = crate::retry::max_attempts(retry, &prepared.method);
+
+    if let Some(until) = &req.until {
+        crate::script::check_condition(until)?;
+    }
+    let mut poll = 1;
+    let mut started;
+    let mut attempt;
+    let mut sent_at;
+    let mut sent;
+    let mut request_bytes;
+    let mut headers_at;
+    let (status, version, headers, received) = loop {
+        // Send, retrying as long as the policy allows
+        started = Instant::now();
+        attempt = 1;
+        let mut digest: Option<HeaderValue> = None;
+        let resp = loop {
+            let request = prepared.build(digest.as_ref())?;
+            sent = Sent {
+                method: request.method().clone(),
+                url: request.url().clone(),
+                headers: header_pairs(request.headers()),
+                body: match request.body() {
+                    Some(body) => body.as_bytes().map(<[u8]>::to_vec),
+                    None => Some(Vec::new()),
+                },
+            };
+            request_bytes = request
+                .body()
+                .map_or(Some(0), |b| b.as_bytes().map(|b| b.len() as u64));
+            sent_at = Instant::now();
+            let result = client.execute(request).await;
+
+            // Digest auth needs the server's challenge: answer the first 401 once.
+            if let (Ok(resp), None) = (&result, &digest) {
+                if let Some(answer) = prepared.digest_answer(resp, &sent.method, &sent.url)? {
+                    digest = Some(answer);
+                    continue;
+                }
+            }
+
+            let delay = match (retry, &result) {
+                (Some(p), Ok(resp)) if crate::retry::retries_status(p, resp.status()) => Some((
+                    crate::retry::retry_after(resp.status(), resp.headers())
+                        .unwrap_or_else(|| crate::retry::backoff(p, attempt)),
+                    resp.status().to_string(),
+                )),
+                (Some(p), Err(e)) if crate::retry::retries_error(p, e) => Some((
+                    crate::retry::backoff(p, attempt),
+                    if e.is_timeout() {
+                        "timeout"
+                    } else {
+                        "connection failed"
+                    }
+                    .to_string(),
+                )),
+                _ => None,
+            };
+
+            match delay {
+                Some((delay, reason)) if attempt < max_attempts => {
+                    attempt += 1;
+                    say!(
+                        opts,
+                        "{}",
+                        format!(
+                            "↻ {reason} – attempt {attempt}/{max_attempts} in {} ms",
+                            delay.as_millis()
+                        )
+                        .yellow()
+                    );
+                    tokio::time::sleep(delay).await;
+                }
+                _ => break result.map_err(|e| send_error(e, &url, transport))?,
+            }
+        };
+        headers_at = Instant::now();
+        let (status, version, headers) = (resp.status(), resp.version(), resp.headers().clone());
+
+        // Poll until the spell's condition holds, judging each response like a test script would
+        let Some(until) = &req.until else {
+            break (status, version, headers, Received::Pending(resp));
+        };
+        let bytes = resp
+            .bytes()
+            .await
+            .context("reading response body")?
+            .to_vec();
+        let content_type = headers.get(CONTENT_TYPE).and_then(|v| v.to_str().ok());
+        let senv = crate::script::ScriptEnv {
+            vars: &mut *vars,
+            status: Some(status.as_u16() as i64),
+            headers: Some(header_map(&headers)),
+            data: Some(&bytes)
+                .filter(|b| !crate::render::is_binary(b, content_type))
+                .and_then(|b| serde_json::from_str(&crate::render::decode(b, content_type)).ok()),
+            body: Some(bytes.clone()),
+            attempts: Some(attempt as i64),
+            polls: Some(poll as i64),
+            timing: None,
+            event: None,
+            project: project.to_string(),
+            env: env.to_string(),
+        };
+        // A condition that cannot be evaluated on this response (say `data.status` on an
+        // empty 202) is simply not met yet.
+        let (met, error) = match crate::script::eval_condition(until, &senv) {
+            Ok(met) => (met, None),
+            Err(e) => (false, Some(e)),
+        };
+        if met {
+            break (status, version, headers, Received::Read(bytes));
+        }
+        if poll >= req.max_attempts {
+            let err = anyhow!(
+                "'{}' still not `{until}` after {poll} attempts (last status {status})",
+                req.name
+            );
+            return Err(match error {
+                Some(e) => e.context(err),
+                None => err,
+            });
+        }
+        poll += 1;
+        say!(
+            opts,
+            "{}",
+            format!(
+                "⟳ {status} – waiting for `{until}`, attempt {poll}/{} in {} ms",
+                req.max_attempts, req.interval_ms
+            )
+            .yellow()
+        );
+        tokio::time::sleep(Duration::from_millis(req.interval_ms)).await;
+    };
+    let response_headers = header_pairs(&headers);
+    let headers_map = header_map(&headers);
+
+    match opts.format {
+        OutputFormat::Transcript => print_head(status, &response_headers),
+        OutputFormat::Stderr => eprint!("{}", head(status, &response_headers)),
+        OutputFormat::Body => eprint!("{}", head(status, &[])),
+        OutputFormat::Headers | OutputFormat::Raw => {
+            print!("{}", raw_head(version, status, &response_headers))
+        }
+        OutputFormat::Json => {}
+    }
+
+    let content_type = crate::render::content_type(&response_headers);
+    let save_to = opts
+        .output
+        .clone()
+        .or_else(|| req.save_to.as_ref().map(PathBuf::from));
+    let event_stream = content_type.is_some_and(|ct| {
+        ct.split(';')
+            .next()
+            .is_some_and(|mime| mime.trim().eq_ignore_ascii_case("text/event-stream"))
+    });
+    let mut events = None;
+    let (body, response_bytes) = match (&save_to, received) {
+        (Some(path), received) => {
+            let written = match received {
+                Received::Pending(resp) => save_body(resp, path).await?,
+                Received::Read(bytes) => {
+                    fs::write(path, &bytes)
+                        .with_context(|| format!("writing {}", path.display()))?;
+                    bytes.len() as u64
+                }
+            };
+            say!(
+                opts,
+                "{}\n",
+                format!("💾 saved {written} bytes to {}", path.display()).green()
+            );
+            (None, written)
+        }
+        (None, Received::Pending(resp)) if event_stream => {
+            let (raw, streamed) = stream_events(resp, req, vars, project, env, opts).await?;
+            events = Some(streamed);
+            let len = raw.len() as u64;
+            (Some(raw), len)
+        }
+        (None, received) => {
+            let bytes = match received {
+                Received::Pending(resp) => resp
+                    .bytes()
+                    .await
+                    .context("reading response body")?
+                    .to_vec(),
+                Received::Read(bytes) => bytes,
+            };
+            let len = bytes.len() as u64;
+            (Some(bytes), len)
+        }
+    };
+    // Measured before rendering, which may sit in the pager for as long as the user reads.
+    let received_at = Instant::now();
+    let timing = Timing {
+        total: received_at - started,
+        ttfb: headers_at - sent_at,
+        download: received_at - headers_at,
+        request_bytes,
+        response_bytes,
+    };
+
+    if let (Some(bytes), None) = (&body, &events) {
+        match opts.format {
+            OutputFormat::Transcript => {
+                let text = crate::render::body(
+                    bytes,
+                    content_type,
+                    opts.max_body,
+                    crate::render::stdout_is_tty(),
+                );
+                match &opts.spinner {
+                    Some(pb) => pb.suspend(|| crate::render::show(&text, opts.pager)),
+                    None => crate::render::show(&text, opts.pager),
+                }
+            }
+            OutputFormat::Stderr => eprintln!(
+                "{}\n",
+                crate::render::body(bytes, content_type, opts.max_body, false)
+            ),
+            OutputFormat::Body | OutputFormat::Raw => {
+                let mut stdout = std::io::stdout().lock();
+                stdout.write_all(bytes)?;
+                stdout.flush()?;
+            }
+            OutputFormat::Headers | OutputFormat::Json => {}
+        }
+    }
+    let text = body
+        .as_deref()
+        .filter(|b| !crate::render::is_binary(b, content_type))
+        .map(|b| crate::render::decode(b, content_type));
+    if opts.timing {
+        say!(opts, "{}\n", timing.to_string().dimmed());
+    }
+
+    if api.history.enabled {
+        crate::db::save_history(&crate::db::HistoryEntry {
+            id: 0,
+            project: project.to_string(),
+            env: env.to_string(),
+            spell: req.name.clone(),
+            method: sent.method.to_string(),
+            url: sent.url.to_string(),
+            request_headers: sent.headers,
+            request_body: sent.body,
+            status: status.as_u16(),
+            response_headers: response_headers.clone(),
+            response_body: body.as_ref().map(|b| b.to_vec()),
+            duration_ms: timing.total.as_millis() as i64,
+            created_at: chrono::Utc::now().timestamp(),
+        })?;
+        let h = &api.history;
+        crate::db::prune_history(project, env, h.keep, h.max_age_days)?;
+    }
+
+    let mut data = text
+        .as_deref()
+        .and_then(|t| serde_json::from_str::<serde_json::Value>(t).ok());
+    let mut items = None;
+    if let Some(paginate) = &req.paginate {
+        let first = data
+            .as_ref()
+            .with_context(|| format!("'{}' paginates but its first page is not JSON", req.name))?;
+        let page = Page {
+            url: sent.url.clone(),
+            headers: headers.clone(),
+            body: first.clone(),
+        };
+        let merged = follow_pages(&prepared, paginate, page, opts).await?;
+        items = Some(merged.clone());
+        data = Some(merged);
+    }
+    if let Some(events) = &events {
+        data = Some(Value::Array(events.clone()));
+    }
+
+    let mut tests = Tests::Skipped;
     if let Some(code) = req.test_script.as_ref().or(req.spell.as_ref()) {
         let mut senv = crate::script::ScriptEnv {
-            vars: &mut vars,
+            vars,
             status: Some(status.as_u16() as i64),
             headers: Some(headers_map),
-            data: serde_json::from_str::<serde_json::Value>(&text).ok(),
+            data,
+            body: body.as_ref().map(|b| b.to_vec()),
+            attempts: Some(attempt as i64),
+            polls: Some(poll as i64),
+            timing: Some(timing.to_json()),
+            event: None,
             project: project.to_string(),
             env: env.to_string(),
         };
-        crate::script::run_script(code, &mut senv)?;
-        println!("{}", "✓ tests passed".green().bold());
+        tests = match crate::script::run_script(code, &mut senv) {
+            Ok(()) => {
+                say!(opts, "{}", "✓ tests passed".green().bold());
+                Tests::Passed
+            }
+            Err(e) => {
+                say!(opts, "{}", "✗ tests failed".red().bold());
+                Tests::Failed(e)
+            }
+        };
     }
 
-    crate::db::upsert_vars(project, env, &vars)?;
+    if opts.format == OutputFormat::Json {
+        let mut headers = serde_json::Map::new();
+        for (k, v) in &response_headers {
+            let joined = match headers.remove(k) {
+                Some(Value::String(prev)) => format!("{prev}, {v}"),
+                _ => v.clone(),
+            };
+            headers.insert(k.clone(), Value::String(joined));
+        }
+        let mut doc = serde_json::json!({
+            "spell": req.name,
+            "method": sent.method.as_str(),
+            "url": sent.url.as_str(),
+            "status": status.as_u16(),
+            "headers": headers,
+            "body": text
+                .as_deref()
+                .map(|t| serde_json::from_str(t).unwrap_or_else(|_| Value::String(t.to_string()))),
+            "body_hex": body.as_deref().filter(|_| text.is_none()).map(hex::encode),
+            "timing": timing.to_json(),
+            "tests": match &tests {
+                Tests::Skipped => "skipped",
+                Tests::Passed => "passed",
+                Tests::Failed(_) => "failed",
+            },
+        });
+        if let Some(items) = items {
+            doc["items"] = items;
+        }
+        if let Some(events) = events {
+            doc["events"] = Value::Array(events);
+        }
+        println!("{}", serde_json::to_string_pretty(&doc)?);
+    }
+
+    crate::db::upsert_vars(project, env, vars)?;
+    session.save_cookies()?;
+
+    Ok(Outcome {
+        status,
+        headers: response_headers,
+        body,
+        timing,
+        tests,
+    })
+}
+
+/// Resends a recorded request exactly as it went out and records the new exchange.
+pub async fn replay(
+    session: &Session,
+    api: &Api,
+    entry: &crate::db::HistoryEntry,
+) -> Result<crate::db::HistoryEntry> {
+    let body = entry.request_body.clone().with_context(|| {
+        format!(
+            "the body of #{} was streamed and not recorded; cast '{}' again instead",
+            entry.id, entry.spell
+        )
+    })?;
+    let method = Method::from_bytes(entry.method.as_bytes())?;
+    let url = reqwest::Url::parse(&entry.url)?;
+    println!("{}", format!("→ {method} {url}").bold());
 
-    Ok(())
+    let mut request = reqwest::Request::new(method, url);
+    for (k, v) in &entry.request_headers {
+        request.headers_mut().append(
+            HeaderName::from_bytes(k.as_bytes())?,
+            HeaderValue::from_str(v)?,
+        );
+    }
+    if !body.is_empty() {
+        *request.body_mut() = Some(body.clone().into());
+    }
+
+    let started = Instant::now();
+    let resp = session
+        .client
+        .execute(request)
+        .await
+        .map_err(|e| send_error(e, &entry.url, &session.transport))?;
+    let status = resp.status();
+    let response_headers = header_pairs(resp.headers());
+    print_head(status, &response_headers);
+    let bytes = resp.bytes().await.context("reading response body")?;
+    let duration = started.elapsed();
+    crate::render::show(
+        &crate::render::body(
+            &bytes,
+            crate::render::content_type(&response_headers),
+            None,
+            crate::render::stdout_is_tty(),
+        ),
+        true,
+    );
+    session.save_cookies()?;
+
+    let mut replayed = crate::db::HistoryEntry {
+        id: 0,
+        project: entry.project.clone(),
+        env: entry.env.clone(),
+        spell: entry.spell.clone(),
+        method: entry.method.clone(),
+        url: entry.url.clone(),
+        request_headers: entry.request_headers.clone(),
+        request_body: Some(body),
+        status: status.as_u16(),
+        response_headers,
+        response_body: Some(bytes.to_vec()),
+        duration_ms: duration.as_millis() as i64,
+        created_at: chrono::Utc::now().timestamp(),
+    };
+    if api.history.enabled {
+        replayed.id = crate::db::save_history(&replayed)?;
+        let h = &api.history;
+        crate::db::prune_history(&entry.project, &entry.env, h.keep, h.max_age_days)?;
+    }
+    Ok(replayed)
 }
@@ -1,14 +1,22 @@
-use anyhow::{Context, Result};
+use anyhow::Result;
 use jsonpath_lib as jsonpath;
 use rhai::{Dynamic, Engine, EvalAltResult, ImmutableString, Map as RhaiMap, Scope};
-use serde_json::{Map as JsonMap, Value};
-use std::{collections::HashMap, fs};
+use serde_json::Value;
+use std::collections::HashMap;
 
 pub struct ScriptEnv<'a> {
     pub vars: &'a mut HashMap<String, String>,
     pub status: Option<i64>,
     pub headers: Option<HashMap<String, String>>,
     pub data: Option<Value>,
+    /// Raw response body, pushed as the `body` blob.
+    pub body: Option<Vec<u8>>,
+    pub attempts: Option<i64>,
+    /// How many times an `until` spell has been sent so far.
+    pub polls: Option<i64>,
+    pub timing: Option<Value>,
+    /// The server-sent event an `on_event` script runs for.
+    pub event: Option<Value>,
     pub project: String,
     pub env: String,
 }
@@ -45,6 +53,24 @@ fn engine() -> Engine {
             }
         },
     );
+    eng.register_fn(
+        "expect_signature",
+        |body: rhai::Blob, hex: &str| -> Result<(), Box<EvalAltResult>> {
+            let magic = hex::decode(hex.replace(' ', ""))
+                .map_err(|e| format!("expect_signature: invalid hex '{hex}': {e}"))?;
+            if body.starts_with(&magic) {
+                Ok(())
+            } else {
+                let head = &body[..body.len().min(magic.len())];
+                Err(format!(
+                    "Assertion failed: body starts with {} not {}",
+                    hex::encode(head),
+                    hex::encode(&magic)
+                )
+                .into())
+            }
+        },
+    );
     eng.register_fn("jsonPath", |data: Dynamic, expr: String| -> Dynamic {
         if let Some(v) = data.try_cast::<serde_json::Value>() {
             match jsonpath::select(&v, &expr) {
@@ -60,16 +86,30 @@ fn engine() -> Engine {
 
     eng
 }
-pub fn run_script(code: &str, senv: &mut ScriptEnv) -> Result<()> {
-    let eng = engine();
+fn scope(senv: &ScriptEnv) -> Result<Scope<'static>> {
     let mut scope = Scope::new();
 
     if let Some(status) = senv.status {
         scope.push("status", status);
     }
+    if let Some(attempts) = senv.attempts {
+        scope.push("attempts", attempts);
+    }
+    if let Some(polls) = senv.polls {
+        scope.push("polls", polls);
+    }
     if let Some(ref headers) = senv.headers {
         scope.push("headers", headers.clone());
     }
+    if let Some(ref timing) = senv.timing {
+        scope.push_dynamic("timing", rhai::serde::to_dynamic(timing.clone())?);
+    }
+    if let Some(ref event) = senv.event {
+        scope.push_dynamic("event", rhai::serde::to_dynamic(event.clone())?);
+    }
+    if let Some(ref body) = senv.body {
+        scope.push("body", body.clone());
+    }
     if let Some(ref data) = senv.data {
         scope.push_dynamic("data", rhai::serde::to_dynamic(data.clone())?);
     }
@@ -82,7 +122,12 @@ pub fn run_script(code: &str, senv: &mut ScriptEnv) -> Result<()> {
         );
     }
     scope.push_dynamic("env", Dynamic::from(env_map));
+    Ok(scope)
+}
 
+pub fn run_script(code: &str, senv: &mut ScriptEnv) -> Result<()> {
+    let eng = engine();
+    let mut scope = scope(senv)?;
     let _ = eng
         .eval_with_scope::<Dynamic>(&mut scope, code)
         .map_err(|e| anyhow::anyhow!("Rhai error: {e}"))?;
@@ -101,3 +146,23 @@ pub fn run_script(code: &str, senv: &mut ScriptEnv) -> Result<()> {
 
     Ok(())
 }
+
+/// Fails on an `until` condition that does not even parse, before any polling.
+pub fn check_condition(code: &str) -> Result<()> {
+    engine()
+        .compile_expression(code)
+        .map_err(|e| anyhow::anyhow!("Rhai error in `until`: {e}"))?;
+    Ok(())
+}
+
+/// Evaluates an `until` condition, which must yield a boolean; `env` changes are discarded.
+/// `data` is `()` when the body is not JSON, e.g. on a 202 or 204 while a job is pending.
+pub fn eval_condition(code: &str, senv: &ScriptEnv) -> Result<bool> {
+    let mut scope = scope(senv)?;
+    if senv.data.is_none() {
+        scope.push("data", ());
+    }
+    engine()
+        .eval_with_scope::<bool>(&mut scope, code)
+        .map_err(|e| anyhow::anyhow!("Rhai error in `until`: {e}"))
+}
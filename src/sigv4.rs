@@ -0,0 +1,153 @@
+use crate::loader::AwsSigv4;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use reqwest::{header::HeaderValue, Request, Url};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+
+const ALGORITHM: &str = "AWS4-HMAC-SHA256";
+
+/// Signs the fully built `request` in place with AWS Signature Version 4.
+pub fn sign(request: &mut Request, cfg: &AwsSigv4) -> Result<()> {
+    sign_at(request, cfg, Utc::now())
+}
+
+/// Like `sign`, with the signing time fixed to `now`.
+pub fn sign_at(request: &mut Request, cfg: &AwsSigv4, now: DateTime<Utc>) -> Result<()> {
+    let amz_date = now.format("%Y%m%dT%H%M%SZ").to_string();
+    let date = now.format("%Y%m%d").to_string();
+
+    // Streaming bodies (multipart) cannot be hashed up front.
+    let payload_hash = match request.body() {
+        Some(body) => match body.as_bytes() {
+            Some(bytes) => hex::encode(Sha256::digest(bytes)),
+            None => "UNSIGNED-PAYLOAD".to_string(),
+        },
+        None => hex::encode(Sha256::digest(b"")),
+    };
+
+    let headers = request.headers_mut();
+    headers.insert("x-amz-date", HeaderValue::from_str(&amz_date)?);
+    if cfg.service == "s3" {
+        headers.insert(
+            "x-amz-content-sha256",
+            HeaderValue::from_str(&payload_hash)?,
+        );
+    }
+    if let Some(token) = &cfg.session_token {
+        headers.insert("x-amz-security-token", HeaderValue::from_str(token)?);
+    }
+
+    let mut canonical_headers: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    canonical_headers.insert("host".into(), vec![host(request.url())?]);
+    for (name, value) in request.headers() {
+        let value = value
+            .to_str()
+            .context("non-ASCII header cannot be signed")?;
+        canonical_headers
+            .entry(name.as_str().to_ascii_lowercase())
+            .or_default()
+            .push(value.split_whitespace().collect::<Vec<_>>().join(" "));
+    }
+    let signed_headers = canonical_headers
+        .keys()
+        .cloned()
+        .collect::<Vec<_>>()
+        .join(";");
+    let canonical_headers: String = canonical_headers
+        .iter()
+        .map(|(k, v)| format!("{k}:{}\n", v.join(",")))
+        .collect();
+
+    let canonical_request = format!(
+        "{}\n{}\n{}\n{}\n{}\n{}",
+        request.method(),
+        canonical_uri(request.url(), cfg.service != "s3"),
+        canonical_query(request.url()),
+        canonical_headers,
+        signed_headers,
+        payload_hash
+    );
+
+    let scope = format!("{date}/{}/{}/aws4_request", cfg.region, cfg.service);
+    let string_to_sign = format!(
+        "{ALGORITHM}\n{amz_date}\n{scope}\n{}",
+        hex::encode(Sha256::digest(canonical_request.as_bytes()))
+    );
+
+    let mut key = hmac(
+        format!("AWS4{}", cfg.secret_key).as_bytes(),
+        date.as_bytes(),
+    );
+    for part in [cfg.region.as_str(), cfg.service.as_str(), "aws4_request"] {
+        key = hmac(&key, part.as_bytes());
+    }
+    let signature = hex::encode(hmac(&key, string_to_sign.as_bytes()));
+
+    let authorization = format!(
+        "{ALGORITHM} Credential={}/{scope}, SignedHeaders={signed_headers}, Signature={signature}",
+        cfg.access_key
+    );
+    request
+        .headers_mut()
+        .insert("authorization", HeaderValue::from_str(&authorization)?);
+    Ok(())
+}
+
+fn hmac(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any size");
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
+
+fn host(url: &Url) -> Result<String> {
+    let host = url.host_str().context("URL has no host")?;
+    Ok(match url.port() {
+        Some(port) => format!("{host}:{port}"),
+        None => host.to_string(),
+    })
+}
+
+/// Every service but S3 expects each path segment to be encoded twice.
+fn canonical_uri(url: &Url, double_encode: bool) -> String {
+    let path = url.path();
+    if !double_encode || path.is_empty() {
+        return if path.is_empty() {
+            "/".into()
+        } else {
+            path.into()
+        };
+    }
+    path.split('/')
+        .map(uri_encode)
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+fn canonical_query(url: &Url) -> String {
+    let mut pairs: Vec<(String, String)> = url
+        .query_pairs()
+        .map(|(k, v)| (uri_encode(&k), uri_encode(&v)))
+        .collect();
+    pairs.sort();
+    pairs
+        .into_iter()
+        .map(|(k, v)| format!("{k}={v}"))
+        .collect::<Vec<_>>()
+        .join("&")
+}
+
+/// Percent-encodes everything but the RFC 3986 unreserved characters, as SigV4 requires.
+fn uri_encode(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for b in s.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                out.push(b as char)
+            }
+            _ => out.push_str(&format!("%{b:02X}")),
+        }
+    }
+    out
+}
@@ -0,0 +1,84 @@
+use serde_json::{json, Value};
+
+/// One server-sent event.
+pub struct Event {
+    pub event: String,
+    pub id: Option<String>,
+    pub data: String,
+}
+
+impl Event {
+    /// The data parsed as JSON when it is, the raw text otherwise.
+    pub fn data_json(&self) -> Value {
+        serde_json::from_str(&self.data).unwrap_or_else(|_| Value::String(self.data.clone()))
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({ "event": self.event, "id": self.id, "data": self.data_json() })
+    }
+}
+
+/// Incremental `text/event-stream` parser: feed it chunks as they arrive.
+#[derive(Default)]
+pub struct Parser {
+    buf: Vec<u8>,
+    event: Option<String>,
+    id: Option<String>,
+    data: Vec<String>,
+}
+
+impl Parser {
+    /// Consumes `chunk` and returns the events it completed.
+    pub fn feed(&mut self, chunk: &[u8]) -> Vec<Event> {
+        self.buf.extend_from_slice(chunk);
+        let mut events = Vec::new();
+        let mut start = 0;
+        while let Some(pos) = self.buf[start..]
+            .iter()
+            .position(|b| *b == b'\n' || *b == b'\r')
+        {
+            let end = start + pos;
+            // A trailing `\r` may be the first half of a `\r\n` split across chunks.
+            if self.buf[end] == b'\r' && end + 1 == self.buf.len() {
+                break;
+            }
+            let line = String::from_utf8_lossy(&self.buf[start..end]).into_owned();
+            start = end + 1;
+            if self.buf[end] == b'\r' && self.buf.get(start) == Some(&b'\n') {
+                start += 1;
+            }
+            if let Some(event) = self.line(&line) {
+                events.push(event);
+            }
+        }
+        self.buf.drain(..start);
+        events
+    }
+
+    fn line(&mut self, line: &str) -> Option<Event> {
+        if line.is_empty() {
+            let event = self.event.take();
+            if self.data.is_empty() {
+                return None;
+            }
+            return Some(Event {
+                event: event.unwrap_or_else(|| "message".into()),
+                id: self.id.clone(),
+                data: std::mem::take(&mut self.data).join("\n"),
+            });
+        }
+        if line.starts_with(':') {
+            return None;
+        }
+        let (field, value) = line.split_once(':').unwrap_or((line, ""));
+        let value = value.strip_prefix(' ').unwrap_or(value);
+        match field {
+            "event" => self.event = Some(value.to_string()),
+            "data" => self.data.push(value.to_string()),
+            // The last event id carries over to the following events.
+            "id" if !value.contains('\0') => self.id = Some(value.to_string()),
+            _ => {}
+        }
+        None
+    }
+}
@@ -0,0 +1,57 @@
+#[cfg(test)]
+mod auth_tests {
+    use qwest::auth::{digest_authorization_with, parse_challenge};
+    use reqwest::{Method, Url};
+
+    /// The SHA-256 challenge of RFC 7616, section 3.9.1.
+    const RFC7616_CHALLENGE: &str = r#"Digest realm="http-auth@example.org",
+        qop="auth, auth-int",
+        algorithm=SHA-256,
+        nonce="7ypf/xlj9XXwfDPEoM4URrv/xwf94BcCAzFZH4GiTo0v",
+        opaque="FQhe/qaU925kfnzjCev0ciny7QMkPqMAFRtzCUYo5tdS""#;
+
+    #[test]
+    fn test_parse_rfc7616_challenge() {
+        let params = parse_challenge(RFC7616_CHALLENGE.strip_prefix("Digest").unwrap());
+
+        assert_eq!(params.len(), 5);
+        assert_eq!(params["realm"], "http-auth@example.org");
+        assert_eq!(params["qop"], "auth, auth-int");
+        assert_eq!(params["algorithm"], "SHA-256");
+        assert_eq!(
+            params["nonce"],
+            "7ypf/xlj9XXwfDPEoM4URrv/xwf94BcCAzFZH4GiTo0v"
+        );
+        assert_eq!(
+            params["opaque"],
+            "FQhe/qaU925kfnzjCev0ciny7QMkPqMAFRtzCUYo5tdS"
+        );
+    }
+
+    #[test]
+    fn test_rfc7616_sha256_response() {
+        let url = Url::parse("http://www.example.org/dir/index.html").unwrap();
+        let header = digest_authorization_with(
+            RFC7616_CHALLENGE,
+            &Method::GET,
+            &url,
+            "Mufasa",
+            "Circle of Life",
+            "f2/wE4q74E6zIJEtWaHKaf5wv/H5QzzpXusqGemxURZJ",
+        )
+        .expect("challenge should be answered");
+        let params = parse_challenge(header.strip_prefix("Digest").unwrap());
+
+        assert_eq!(
+            params["response"],
+            "753927fa0e85d155564e2e272a28d1802ca10daf4496794697cf8db5856cb6c1"
+        );
+        assert_eq!(params["uri"], "/dir/index.html");
+        assert_eq!(params["qop"], "auth");
+        assert_eq!(params["nc"], "00000001");
+        assert_eq!(
+            params["opaque"],
+            "FQhe/qaU925kfnzjCev0ciny7QMkPqMAFRtzCUYo5tdS"
+        );
+    }
+}
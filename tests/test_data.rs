@@ -0,0 +1,63 @@
+#[cfg(test)]
+mod data_tests {
+    use qwest::data::load_rows;
+    use std::fs;
+    use std::path::Path;
+
+    fn row(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_load_csv() {
+        fs::write(
+            "/tmp/test-qwest-rows.csv",
+            " id , name\n1,Ada\n2,Grace Hopper\n",
+        )
+        .expect("couldn't create test fixture");
+        let rows = load_rows(Path::new("/tmp/test-qwest-rows.csv")).expect("Failed to load rows");
+        assert_eq!(
+            rows,
+            [
+                row(&[("id", "1"), ("name", "Ada")]),
+                row(&[("id", "2"), ("name", "Grace Hopper")]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_load_jsonl() {
+        fs::write(
+            "/tmp/test-qwest-rows.jsonl",
+            "{\"id\": 1, \"tags\": [\"a\"], \"meta\": {\"x\": true}, \"note\": null}\n\n  \n{\"id\": 2}\n",
+        )
+        .expect("couldn't create test fixture");
+        let rows = load_rows(Path::new("/tmp/test-qwest-rows.jsonl")).expect("Failed to load rows");
+        assert_eq!(rows.len(), 2);
+        let first: std::collections::HashMap<_, _> = rows[0].iter().cloned().collect();
+        assert_eq!(first["id"], "1");
+        assert_eq!(first["tags"], r#"["a"]"#);
+        assert_eq!(first["meta"], r#"{"x":true}"#);
+        assert_eq!(first["note"], "");
+        assert_eq!(rows[1], row(&[("id", "2")]));
+    }
+
+    #[test]
+    fn test_load_rows_errors() {
+        fs::write("/tmp/test-qwest-bad-rows.jsonl", "{\"id\": 1}\n[1, 2]\n")
+            .expect("couldn't create test fixture");
+        let err = load_rows(Path::new("/tmp/test-qwest-bad-rows.jsonl")).unwrap_err();
+        assert!(
+            format!("{err:#}").contains("line 2 should be a JSON object"),
+            "{err:#}"
+        );
+
+        fs::write("/tmp/test-qwest-empty-rows.csv", "id,name\n")
+            .expect("couldn't create test fixture");
+        let err = load_rows(Path::new("/tmp/test-qwest-empty-rows.csv")).unwrap_err();
+        assert!(err.to_string().contains("has no rows"), "{err}");
+    }
+}
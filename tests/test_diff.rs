@@ -0,0 +1,58 @@
+#[cfg(test)]
+mod diff_tests {
+    use qwest::diff::{headers, json, Change};
+    use serde_json::json;
+
+    fn pairs(list: &[(&str, &str)]) -> Vec<(String, String)> {
+        list.iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_json_paths() {
+        let old = json!({ "a": [{ "b": 1, "c": true }], "gone": "x", "items": [1, 2] });
+        let new = json!({ "items": [1, 3, 4], "a": [{ "b": 2, "c": true }], "new": null });
+
+        assert_eq!(
+            json(&old, &new),
+            [
+                Change::Changed("$.a[0].b".into(), json!(1), json!(2)),
+                Change::Removed("$.gone".into(), json!("x")),
+                Change::Changed("$.items[1]".into(), json!(2), json!(3)),
+                Change::Added("$.items[2]".into(), json!(4)),
+                Change::Added("$.new".into(), json!(null)),
+            ]
+        );
+        assert!(json(&old, &old).is_empty());
+    }
+
+    #[test]
+    fn test_headers() {
+        let old = pairs(&[
+            ("Set-Cookie", "a=1"),
+            ("set-cookie", "b=2"),
+            ("Date", "Mon"),
+            ("X-Old", "1"),
+        ]);
+        let new = pairs(&[("set-cookie", "a=1"), ("DATE", "Tue"), ("X-New", "1")]);
+
+        assert_eq!(
+            headers(&old, &new, &["date".to_string()]),
+            [
+                Change::Changed("set-cookie".into(), json!("a=1, b=2"), json!("a=1")),
+                Change::Removed("x-old".into(), json!("1")),
+                Change::Added("x-new".into(), json!("1")),
+            ]
+        );
+        // `--ignore-header` names match whatever their case.
+        assert_eq!(
+            headers(
+                &pairs(&[("Date", "Mon")]),
+                &pairs(&[("date", "Tue")]),
+                &["DaTe".into()]
+            ),
+            []
+        );
+    }
+}
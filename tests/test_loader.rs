@@ -27,17 +27,169 @@ return map;
 	value = "application/x-www-form-urlencoded"
 "#;
 
+pub const TEMPLATE_DEPS: &str = r#"
+[api]
+name = "deps"
+base_url = "https://api.example.com"
+
+[[request]]
+name = "get_users"
+method = "GET"
+path = "/users"
+depends_on = ["login"]
+
+[[request]]
+name = "login"
+method = "POST"
+path = "/login"
+depends_on = ["docs"]
+
+[[request]]
+name = "docs"
+method = "GET"
+path = "/docs"
+
+[[request]]
+name = "ping"
+method = "GET"
+path = "/ping"
+depends_on = ["pong"]
+
+[[request]]
+name = "pong"
+method = "GET"
+path = "/pong"
+depends_on = ["ping"]
+"#;
+
+pub const TEMPLATE_MULTIPART: &str = r#"
+[api]
+name = "uploads"
+base_url = "https://api.example.com"
+
+[[request]]
+name = "upload"
+method = "POST"
+path = "/documents"
+
+	[[request.part]]
+	name = "title"
+	value = "Quarterly report"
+
+	[[request.part]]
+	name = "document"
+	file = "${DOCS}/report.pdf"
+	content_type = "application/pdf"
+"#;
+
+pub const TEMPLATE_RAW_BODIES: &str = r#"
+[api]
+name = "raw"
+base_url = "https://api.example.com"
+
+[[request]]
+name = "soap"
+method = "POST"
+path = "/soap"
+body_type = "xml"
+body = "<Envelope><Body/></Envelope>"
+
+[[request]]
+name = "upload"
+method = "PUT"
+path = "/blob"
+body_file = "payload.bin"
+"#;
+
+pub const TEMPLATE_AUTH: &str = r#"
+[api]
+name = "auth"
+base_url = "https://api.example.com"
+
+[api.auth]
+type = "bearer"
+token = "${TOKEN}"
+
+[[request]]
+name = "me"
+method = "GET"
+path = "/me"
+
+[[request]]
+name = "search"
+method = "GET"
+path = "/search"
+[request.auth]
+type = "api_key"
+name = "api_key"
+value = "${API_KEY}"
+in = "query"
+"#;
+
+pub const TEMPLATE_BENCH: &str = r#"
+[api]
+name = "bench"
+base_url = "https://api.example.com"
+
+[bench]
+rate = 200
+thresholds = ["p95 < 300ms", "error_rate < 1%", "p99.9 <= 1.5s"]
+
+[[bench.stage]]
+duration = "30s"
+target = 50
+
+[[bench.stage]]
+duration = "2m"
+target = 50
+
+[[request]]
+name = "me"
+method = "GET"
+path = "/me"
+"#;
+
+pub const TEMPLATE_PAGINATE: &str = r#"
+[api]
+name = "paginate"
+base_url = "https://api.example.com"
+
+[[request]]
+name = "users"
+method = "GET"
+path = "/users"
+[request.paginate]
+style = "cursor"
+cursor = "$.meta.next"
+param = "after"
+items = "$.data"
+
+[[request]]
+name = "orders"
+method = "GET"
+path = "/orders"
+[request.paginate]
+style = "page"
+items = "$"
+max_pages = 3
+"#;
+
 #[cfg(test)]
 mod loader_tests {
-    use super::TEMPLATE_1;
+    use super::{
+        TEMPLATE_1, TEMPLATE_AUTH, TEMPLATE_BENCH, TEMPLATE_DEPS, TEMPLATE_MULTIPART,
+        TEMPLATE_PAGINATE, TEMPLATE_RAW_BODIES,
+    };
     use qwest::load_config;
+    use qwest::loader::{ApiKeyLocation, Auth, BodyType, Comparison, Metric, PageStyle};
     use std::collections::HashMap;
     use std::fs;
+    use std::time::Duration;
 
     #[test]
     fn test_load_config() {
         fs::write("/tmp/test-qwest.toml", TEMPLATE_1).expect("couldn't create test fixture");
-        let mut vars = HashMap::new();
+        let vars = HashMap::new();
         let config = load_config("/tmp/test-qwest.toml", vars).expect("Failed to load config");
         assert_eq!(config.api.name, "test_1");
         assert_eq!(config.api.base_url, "https://api.example.com");
@@ -62,4 +214,159 @@ mod loader_tests {
         assert!(!second_request.headers.is_empty());
         assert!(second_request.body.is_some());
     }
+
+    #[test]
+    fn test_resolve_dependencies() {
+        fs::write("/tmp/test-qwest-deps.toml", TEMPLATE_DEPS)
+            .expect("couldn't create test fixture");
+        let config = load_config("/tmp/test-qwest-deps.toml", HashMap::new())
+            .expect("Failed to load config");
+
+        let order: Vec<&str> = config
+            .resolve_order("get_users")
+            .expect("Failed to resolve dependencies")
+            .iter()
+            .map(|r| r.name.as_str())
+            .collect();
+        assert_eq!(order, vec!["docs", "login", "get_users"]);
+
+        let err = config.resolve_order("ping").unwrap_err().to_string();
+        assert!(err.contains("dependency cycle"), "{err}");
+    }
+
+    #[test]
+    fn test_load_multipart_parts() {
+        fs::write("/tmp/test-qwest-multipart.toml", TEMPLATE_MULTIPART)
+            .expect("couldn't create test fixture");
+        let vars = HashMap::from([("DOCS".to_string(), "/srv/docs".to_string())]);
+        let config =
+            load_config("/tmp/test-qwest-multipart.toml", vars).expect("Failed to load config");
+
+        let parts = &config.requests[0].parts;
+        assert_eq!(parts.len(), 2);
+        assert_eq!(parts[0].value.as_deref(), Some("Quarterly report"));
+        assert!(parts[0].file.is_none());
+        assert_eq!(parts[1].file.as_deref(), Some("/srv/docs/report.pdf"));
+        assert_eq!(parts[1].content_type.as_deref(), Some("application/pdf"));
+    }
+
+    #[test]
+    fn test_load_raw_bodies() {
+        fs::write("/tmp/test-qwest-raw.toml", TEMPLATE_RAW_BODIES)
+            .expect("couldn't create test fixture");
+        let config =
+            load_config("/tmp/test-qwest-raw.toml", HashMap::new()).expect("Failed to load config");
+
+        let soap = &config.requests[0];
+        assert_eq!(soap.body_type, Some(BodyType::Xml));
+        assert_eq!(soap.body.as_deref(), Some("<Envelope><Body/></Envelope>"));
+        assert!(soap.json_body().unwrap().is_none());
+
+        let upload = &config.requests[1];
+        assert_eq!(upload.body_file.as_deref(), Some("/tmp/payload.bin"));
+        assert!(upload.body.is_none());
+
+        fs::write(
+            "/tmp/test-qwest-bad-json.toml",
+            TEMPLATE_RAW_BODIES.replace("body_type = \"xml\"\n", ""),
+        )
+        .expect("couldn't create test fixture");
+        let err = load_config("/tmp/test-qwest-bad-json.toml", HashMap::new()).unwrap_err();
+        assert!(err.to_string().contains("invalid JSON body"), "{err}");
+    }
+
+    #[test]
+    fn test_load_auth() {
+        fs::write("/tmp/test-qwest-auth.toml", TEMPLATE_AUTH)
+            .expect("couldn't create test fixture");
+        let vars = HashMap::from([
+            ("TOKEN".to_string(), "t0k3n".to_string()),
+            ("API_KEY".to_string(), "k3y".to_string()),
+        ]);
+        let config = load_config("/tmp/test-qwest-auth.toml", vars).expect("Failed to load config");
+
+        assert!(matches!(&config.api.auth, Some(Auth::Bearer { token }) if token == "t0k3n"));
+        assert!(config.requests[0].auth.is_none());
+        assert!(matches!(
+            &config.requests[1].auth,
+            Some(Auth::ApiKey { name, value, location: ApiKeyLocation::Query })
+                if name == "api_key" && value == "k3y"
+        ));
+    }
+
+    #[test]
+    fn test_load_bench() {
+        fs::write("/tmp/test-qwest-bench.toml", TEMPLATE_BENCH)
+            .expect("couldn't create test fixture");
+        let config = load_config("/tmp/test-qwest-bench.toml", HashMap::new())
+            .expect("Failed to load config");
+
+        assert_eq!(config.bench.rate, Some(200.0));
+        assert_eq!(config.bench.stages.len(), 2);
+        assert_eq!(config.bench.stages[1].duration, Duration::from_secs(120));
+        let thresholds: Vec<_> = config
+            .bench
+            .thresholds
+            .iter()
+            .map(|t| (t.metric, t.op, t.value))
+            .collect();
+        assert_eq!(
+            thresholds,
+            [
+                (Metric::Percentile(95.0), Comparison::Lt, 300.0),
+                (Metric::ErrorRate, Comparison::Lt, 1.0),
+                (Metric::Percentile(99.9), Comparison::Le, 1500.0),
+            ]
+        );
+
+        fs::write(
+            "/tmp/test-qwest-bad-bench.toml",
+            TEMPLATE_BENCH.replace("error_rate < 1%", "error_rate < 1ms"),
+        )
+        .expect("couldn't create test fixture");
+        let err = load_config("/tmp/test-qwest-bad-bench.toml", HashMap::new()).unwrap_err();
+        assert!(format!("{err:#}").contains("unexpected unit"), "{err:#}");
+
+        fs::write(
+            "/tmp/test-qwest-zero-rate.toml",
+            TEMPLATE_BENCH.replace("rate = 200", "rate = 0"),
+        )
+        .expect("couldn't create test fixture");
+        let err = load_config("/tmp/test-qwest-zero-rate.toml", HashMap::new()).unwrap_err();
+        assert!(
+            format!("{err:#}").contains("rate should be above 0"),
+            "{err:#}"
+        );
+
+        fs::write(
+            "/tmp/test-qwest-negative-target.toml",
+            TEMPLATE_BENCH.replace("target = 50\n\n[[request]]", "target = -5\n\n[[request]]"),
+        )
+        .expect("couldn't create test fixture");
+        let err = load_config("/tmp/test-qwest-negative-target.toml", HashMap::new()).unwrap_err();
+        assert!(format!("{err:#}").contains("stage target"), "{err:#}");
+    }
+
+    #[test]
+    fn test_load_paginate() {
+        fs::write("/tmp/test-qwest-paginate.toml", TEMPLATE_PAGINATE)
+            .expect("couldn't create test fixture");
+        let config = load_config("/tmp/test-qwest-paginate.toml", HashMap::new())
+            .expect("Failed to load config");
+
+        let users = config.requests[0].paginate.as_ref().unwrap();
+        assert!(matches!(
+            &users.style,
+            PageStyle::Cursor { cursor, param } if cursor == "$.meta.next" && param == "after"
+        ));
+        assert_eq!(users.items, "$.data");
+        assert_eq!(users.max_pages, 10);
+
+        let orders = config.requests[1].paginate.as_ref().unwrap();
+        assert!(matches!(
+            &orders.style,
+            PageStyle::Page { param, start: 1 } if param == "page"
+        ));
+        assert_eq!(orders.max_pages, 3);
+    }
 }
@@ -0,0 +1,61 @@
+#[cfg(test)]
+mod paginate_tests {
+    use qwest::paginate::{next_link, page_items, with_param};
+    use reqwest::header::{HeaderMap, HeaderValue, LINK};
+    use reqwest::Url;
+    use serde_json::json;
+
+    #[test]
+    fn test_next_link() {
+        let url = Url::parse("https://api.example.com/v1/users?page=2").unwrap();
+        let mut headers = HeaderMap::new();
+        headers.append(
+            LINK,
+            HeaderValue::from_static(r#"</v1/users?page=1>; rel="prev first""#),
+        );
+        headers.append(
+            LINK,
+            HeaderValue::from_static(
+                r#"<https://cdn.example.com/x>; rel=preload, <users?page=3>; rel="next last""#,
+            ),
+        );
+        assert_eq!(
+            next_link(&url, &headers).unwrap().as_str(),
+            "https://api.example.com/v1/users?page=3"
+        );
+
+        let mut last = HeaderMap::new();
+        last.insert(
+            LINK,
+            HeaderValue::from_static(r#"</v1/users?page=1>; rel="first""#),
+        );
+        assert!(next_link(&url, &last).is_none());
+    }
+
+    #[test]
+    fn test_with_param_replaces_value() {
+        let url = Url::parse("https://api.example.com/orders?page=1&limit=10").unwrap();
+        assert_eq!(
+            with_param(&url, "page", "2").as_str(),
+            "https://api.example.com/orders?limit=10&page=2"
+        );
+        assert_eq!(
+            with_param(&url, "after", "c 1").as_str(),
+            "https://api.example.com/orders?page=1&limit=10&after=c+1"
+        );
+    }
+
+    #[test]
+    fn test_page_items() {
+        let body = json!({ "data": [{ "id": 1 }, { "id": 2 }] });
+        assert_eq!(
+            page_items(&body, "$.data").unwrap(),
+            [json!({ "id": 1 }), json!({ "id": 2 })]
+        );
+        assert_eq!(
+            page_items(&body, "$.data[*].id").unwrap(),
+            [json!(1), json!(2)]
+        );
+        assert!(page_items(&body, "$.missing").unwrap().is_empty());
+    }
+}
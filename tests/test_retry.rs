@@ -0,0 +1,75 @@
+#[cfg(test)]
+mod retry_tests {
+    use qwest::loader::{Retry, RetryError};
+    use qwest::retry::{backoff, max_attempts, retry_after, MAX_RETRY_AFTER};
+    use reqwest::header::{HeaderMap, HeaderValue, RETRY_AFTER};
+    use reqwest::{Method, StatusCode};
+    use std::time::Duration;
+
+    fn policy(idempotent_only: bool) -> Retry {
+        Retry {
+            max_attempts: 3,
+            backoff_ms: 100,
+            jitter_ms: 0,
+            on_status: vec![429, 503],
+            on_errors: vec![RetryError::Timeout],
+            idempotent_only,
+        }
+    }
+
+    fn retry_after_header(value: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(RETRY_AFTER, HeaderValue::from_str(value).unwrap());
+        headers
+    }
+
+    #[test]
+    fn test_backoff_doubles_up_to_cap() {
+        let p = policy(true);
+        assert_eq!(backoff(&p, 1), Duration::from_millis(100));
+        assert_eq!(backoff(&p, 2), Duration::from_millis(200));
+        assert_eq!(backoff(&p, 3), Duration::from_millis(400));
+        assert_eq!(backoff(&p, 17), Duration::from_millis(100 << 16));
+        assert_eq!(backoff(&p, 40), Duration::from_millis(100 << 16));
+    }
+
+    #[test]
+    fn test_retry_after() {
+        let seconds = retry_after_header("2");
+        assert_eq!(
+            retry_after(StatusCode::SERVICE_UNAVAILABLE, &seconds),
+            Some(Duration::from_secs(2))
+        );
+        // Only 429 and 503 ask the client to wait.
+        assert_eq!(
+            retry_after(StatusCode::INTERNAL_SERVER_ERROR, &seconds),
+            None
+        );
+
+        let past = retry_after_header("Wed, 21 Oct 2015 07:28:00 GMT");
+        assert_eq!(
+            retry_after(StatusCode::TOO_MANY_REQUESTS, &past),
+            Some(Duration::ZERO)
+        );
+        let soon = (chrono::Utc::now() + chrono::Duration::seconds(30)).to_rfc2822();
+        let wait = retry_after(StatusCode::TOO_MANY_REQUESTS, &retry_after_header(&soon)).unwrap();
+        assert!(
+            wait > Duration::from_secs(25) && wait <= Duration::from_secs(30),
+            "{wait:?}"
+        );
+
+        let day = retry_after_header("86400");
+        assert_eq!(
+            retry_after(StatusCode::TOO_MANY_REQUESTS, &day),
+            Some(MAX_RETRY_AFTER)
+        );
+    }
+
+    #[test]
+    fn test_max_attempts() {
+        assert_eq!(max_attempts(Some(&policy(true)), &Method::GET), 3);
+        assert_eq!(max_attempts(Some(&policy(true)), &Method::POST), 1);
+        assert_eq!(max_attempts(Some(&policy(false)), &Method::POST), 3);
+        assert_eq!(max_attempts(None, &Method::GET), 1);
+    }
+}
@@ -0,0 +1,31 @@
+#[cfg(test)]
+mod sigv4_tests {
+    use chrono::{TimeZone, Utc};
+    use qwest::loader::AwsSigv4;
+    use qwest::sigv4::sign_at;
+    use reqwest::{Method, Request, Url};
+
+    /// `get-vanilla` from the AWS SigV4 test suite.
+    #[test]
+    fn test_sign_get_vanilla() {
+        let cfg = AwsSigv4 {
+            region: "us-east-1".into(),
+            service: "service".into(),
+            access_key: "AKIDEXAMPLE".into(),
+            secret_key: "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY".into(),
+            session_token: None,
+        };
+        let url = Url::parse("https://example.amazonaws.com/").unwrap();
+        let mut request = Request::new(Method::GET, url);
+        let now = Utc.with_ymd_and_hms(2015, 8, 30, 12, 36, 0).unwrap();
+        sign_at(&mut request, &cfg, now).expect("signing failed");
+
+        assert_eq!(request.headers()["x-amz-date"], "20150830T123600Z");
+        assert_eq!(
+            request.headers()["authorization"],
+            "AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/20150830/us-east-1/service/aws4_request, \
+             SignedHeaders=host;x-amz-date, \
+             Signature=5fa00fa31553b73ebf1942676e86291e8372ff2a2260956d9b8aae1d763fbf31"
+        );
+    }
+}
@@ -0,0 +1,25 @@
+#[cfg(test)]
+mod sse_tests {
+    use qwest::sse::Parser;
+
+    #[test]
+    fn test_parse_split_stream() {
+        let stream = ": keep-alive\n\nid: 7\nevent: progress\ndata: {\"step\": 1}\n\n\
+                      data: line one\r\ndata: line two\r\n\r\nretry: 10\n\n";
+        let mut parser = Parser::default();
+        // Feed byte by byte so lines and `\r\n` pairs are split across chunks.
+        let events: Vec<_> = stream
+            .as_bytes()
+            .chunks(1)
+            .flat_map(|chunk| parser.feed(chunk))
+            .collect();
+
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].event, "progress");
+        assert_eq!(events[0].id.as_deref(), Some("7"));
+        assert_eq!(events[0].data_json()["step"], 1);
+        assert_eq!(events[1].event, "message");
+        assert_eq!(events[1].id.as_deref(), Some("7"));
+        assert_eq!(events[1].data, "line one\nline two");
+    }
+}